@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use instructions::Instruction;
+use memory::PROGRAM_START;
+use Error;
+
+/// How deep `INCLUDE`s may nest, which also stops a file including itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A line of source, with where it came from for error messages.
+struct Line {
+    file: String,
+    number: usize,
+    text: String,
+}
+
+impl Line {
+    fn error(&self, e: Error) -> Error {
+        format_err!("{}:{}: {}", self.file, self.number, e)
+    }
+}
+
+/// An instruction or data directive, split into its mnemonic and operands.
+struct Statement<'a> {
+    line: &'a Line,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Register(u8),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long(i64),
+    Value(i64),
+}
+
+/// Assembles mnemonic source, in the syntax `Instruction` is displayed in,
+/// into a program to be loaded at `0x200`.
+///
+/// Besides instructions, a line may hold:
+///
+/// * a label, `name:`, optionally followed by an instruction;
+/// * a constant, `name EQU value`;
+/// * data, `DB byte, ...` or `DW word, ...`;
+/// * `INCLUDE "file"`, which assembles another file in place. Paths are
+///   relative to the working directory.
+///
+/// Values are decimal, `0x` hex or `0b` binary numbers, labels or constants,
+/// and may be added to or subtracted from each other. `;` starts a comment.
+pub fn assemble(source: &str) -> Result<Vec<u8>, Error> {
+    let mut lines = vec![];
+    read_lines(source, "<source>", Path::new(""), 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+/// Assembles a source file, as `assemble` does, with `INCLUDE` paths taken
+/// relative to the including file.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let mut lines = vec![];
+    include(path.as_ref(), 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+fn include(path: &Path, depth: usize, lines: &mut Vec<Line>) -> Result<(), Error> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|e| format_err!("Can't read \"{}\": {}", path.display(), e))?;
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    read_lines(&source, &path.display().to_string(), directory, depth, lines)
+}
+
+fn read_lines(source: &str,
+              file: &str,
+              directory: &Path,
+              depth: usize,
+              lines: &mut Vec<Line>)
+              -> Result<(), Error> {
+    for (number, text) in source.lines().enumerate() {
+        let text = strip_comment(text).trim();
+        let mut words = text.splitn(2, char::is_whitespace);
+        if words.next().map(|word| word.eq_ignore_ascii_case("INCLUDE")) != Some(true) {
+            lines.push(Line {
+                file: file.to_string(),
+                number: number + 1,
+                text: text.to_string(),
+            });
+            continue;
+        }
+
+        let name = words.next().unwrap_or("").trim().trim_matches('"');
+        if name.is_empty() {
+            return Err(format_err!("{}:{}: INCLUDE expects a file name", file, number + 1));
+        }
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(format_err!("{}:{}: Includes are nested too deeply", file, number + 1));
+        }
+        include(&directory.join(name), depth + 1, lines)?;
+    }
+
+    Ok(())
+}
+
+fn strip_comment(text: &str) -> &str {
+    match text.find(';') {
+        Some(idx) => &text[..idx],
+        None => text,
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn assemble_lines(lines: &[Line]) -> Result<Vec<u8>, Error> {
+    let mut symbols = HashMap::new();
+    let mut statements = vec![];
+
+    // First pass: place labels and evaluate constants. Every statement's
+    // size is known from its text alone, so labels may be used before
+    // they're defined.
+    let mut address = PROGRAM_START as i64;
+    for line in lines {
+        let mut text = line.text.as_str();
+        while let Some(idx) = text.find(':') {
+            let name = text[..idx].trim();
+            if !is_identifier(name) {
+                break;
+            }
+            define(&mut symbols, name, address).map_err(|e| line.error(e))?;
+            text = text[idx + 1..].trim();
+        }
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.len() >= 3 && words[1].eq_ignore_ascii_case("EQU") {
+            if !is_identifier(words[0]) {
+                return Err(line.error(format_err!("\"{}\" is not a valid name", words[0])));
+            }
+            let value = evaluate(&words[2..].join(" "), &symbols).map_err(|e| line.error(e))?;
+            define(&mut symbols, words[0], value).map_err(|e| line.error(e))?;
+            continue;
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut parts = text.splitn(2, char::is_whitespace);
+        let mnemonic = parts.next().unwrap().to_uppercase();
+        let operands: Vec<&str> = match parts.next().map(str::trim) {
+            Some(operands) if !operands.is_empty() => {
+                operands.split(',').map(str::trim).collect()
+            }
+            _ => vec![],
+        };
+
+        address += match mnemonic.as_str() {
+            "DB" => operands.len() as i64,
+            "DW" => operands.len() as i64 * 2,
+            "LD" if operands.len() == 2 && is_long(operands[1]) => 4,
+            _ => 2,
+        };
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    // Second pass: encode
+    let mut program = vec![];
+    for statement in statements.iter() {
+        let bytes = encode(statement, &symbols).map_err(|e| statement.line.error(e))?;
+        program.extend(bytes);
+    }
+
+    Ok(program)
+}
+
+fn define(symbols: &mut HashMap<String, i64>, name: &str, value: i64) -> Result<(), Error> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(format_err!("\"{}\" is already defined", name));
+    }
+
+    Ok(())
+}
+
+fn is_long(operand: &str) -> bool {
+    match (operand.get(..4), operand.get(4..)) {
+        (Some(keyword), Some(rest)) => {
+            keyword.eq_ignore_ascii_case("LONG") && rest.starts_with(char::is_whitespace)
+        }
+        _ => false,
+    }
+}
+
+/// Evaluates a sum of numbers and symbols, such as `sprites + 5`.
+fn evaluate(text: &str, symbols: &HashMap<String, i64>) -> Result<i64, Error> {
+    let mut total = 0;
+    let mut negative = false;
+    let mut term = String::new();
+    for c in text.chars().chain(Some('+')) {
+        let empty = term.trim().is_empty();
+        match c {
+            '+' | '-' if !empty => {
+                let value = value(term.trim(), symbols)?;
+                total += if negative { -value } else { value };
+                term.clear();
+                negative = c == '-';
+            }
+            '-' => negative = !negative,
+            '+' => return Err(format_err!("\"{}\" is not a valid value", text)),
+            _ => term.push(c),
+        }
+    }
+
+    Ok(total)
+}
+
+fn value(text: &str, symbols: &HashMap<String, i64>) -> Result<i64, Error> {
+    let lower = text.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse()
+    } else {
+        return match symbols.get(text) {
+            Some(value) => Ok(*value),
+            None => Err(format_err!("Unknown label or constant \"{}\"", text)),
+        };
+    };
+
+    parsed.map_err(|_| format_err!("\"{}\" is not a valid number", text))
+}
+
+fn operand(text: &str, symbols: &HashMap<String, i64>) -> Result<Operand, Error> {
+    let upper = text.to_uppercase();
+    if upper.len() == 2 && upper.starts_with('V') {
+        if let Ok(register) = u8::from_str_radix(&upper[1..], 16) {
+            return Ok(Operand::Register(register));
+        }
+    }
+
+    Ok(match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        "R" => Operand::Flags,
+        _ if is_long(text) => Operand::Long(evaluate(text[4..].trim(), symbols)?),
+        _ => Operand::Value(evaluate(text, symbols)?),
+    })
+}
+
+fn in_range(value: i64, min: i64, max: i64) -> Result<i64, Error> {
+    if value < min || value > max {
+        return Err(format_err!("{:#x} is out of range", value));
+    }
+
+    Ok(value)
+}
+
+fn nibble(value: i64) -> Result<u8, Error> {
+    in_range(value, 0, 0xF).map(|value| value as u8)
+}
+
+/// Bytes may also be given as negative numbers, e.g. `ADD V0, -1`.
+fn byte(value: i64) -> Result<u8, Error> {
+    in_range(value, -0x80, 0xFF).map(|value| value as u8)
+}
+
+fn word(value: i64) -> Result<u16, Error> {
+    in_range(value, 0, 0xFFFF).map(|value| value as u16)
+}
+
+fn address(value: i64) -> Result<u16, Error> {
+    in_range(value, 0, 0xFFF).map(|value| value as u16)
+}
+
+fn encode(statement: &Statement, symbols: &HashMap<String, i64>) -> Result<Vec<u8>, Error> {
+    use self::Operand::*;
+
+    let mut operands = vec![];
+    for text in statement.operands.iter() {
+        operands.push(operand(text, symbols)?);
+    }
+
+    let mnemonic = statement.mnemonic.as_str();
+    let instruction = match (mnemonic, &operands[..]) {
+        ("DB", _) | ("DW", _) => {
+            let mut bytes = vec![];
+            for operand in operands.iter() {
+                match (mnemonic, *operand) {
+                    ("DB", Value(value)) => bytes.push(byte(value)?),
+                    ("DW", Value(value)) => {
+                        let value = word(value)?;
+                        bytes.push((value >> 8) as u8);
+                        bytes.push(value as u8);
+                    }
+                    _ => return Err(format_err!("{} expects values", mnemonic)),
+                }
+            }
+            return Ok(bytes);
+        }
+        ("LD", &[I, Long(value)]) => {
+            let value = word(value)?;
+            return Ok(vec![0xF0, 0x00, (value >> 8) as u8, value as u8]);
+        }
+
+        ("CLS", &[]) => Instruction::ClearDisplay,
+        ("RET", &[]) => Instruction::Return,
+        ("SCD", &[Value(n)]) => Instruction::ScrollDown(nibble(n)?),
+        ("SCU", &[Value(n)]) => Instruction::ScrollUp(nibble(n)?),
+        ("SCR", &[]) => Instruction::ScrollRight,
+        ("SCL", &[]) => Instruction::ScrollLeft,
+        ("EXIT", &[]) => Instruction::Exit,
+        ("LOW", &[]) => Instruction::LowRes,
+        ("HIGH", &[]) => Instruction::HighRes,
+        ("JP", &[Value(nnn)]) => Instruction::JumpToAddress(address(nnn)?),
+        ("JP", &[Register(0), Value(nnn)]) => Instruction::JumpToV0Address(address(nnn)?),
+        ("CALL", &[Value(nnn)]) => Instruction::CallSubroutine(address(nnn)?),
+        ("SE", &[Register(x), Value(kk)]) => Instruction::SkipIfEqual(x, byte(kk)?),
+        ("SE", &[Register(x), Register(y)]) => Instruction::SkipIfEqualRegister(x, y),
+        ("SNE", &[Register(x), Value(kk)]) => Instruction::SkipIfNotEqual(x, byte(kk)?),
+        ("SNE", &[Register(x), Register(y)]) => Instruction::SkipIfNotEqualRegister(x, y),
+        ("SAVE", &[Register(x), Register(y)]) => Instruction::SaveRange(x, y),
+        ("LOAD", &[Register(x), Register(y)]) => Instruction::LoadRange(x, y),
+        ("LD", &[Register(x), Value(kk)]) => Instruction::LoadConst(x, byte(kk)?),
+        ("LD", &[Register(x), Register(y)]) => Instruction::AssignValue(x, y),
+        ("LD", &[I, Value(nnn)]) => Instruction::SetMemoryAddress(address(nnn)?),
+        ("LD", &[Register(x), DelayTimer]) => Instruction::LoadDelay(x),
+        ("LD", &[Register(x), Key]) => Instruction::WaitForPress(x),
+        ("LD", &[DelayTimer, Register(x)]) => Instruction::SetDelay(x),
+        ("LD", &[SoundTimer, Register(x)]) => Instruction::SetSound(x),
+        ("LD", &[Font, Register(x)]) => Instruction::SetMemoryForFont(x),
+        ("LD", &[BigFont, Register(x)]) => Instruction::SetMemoryForBigFont(x),
+        ("LD", &[Bcd, Register(x)]) => Instruction::SetBCD(x),
+        ("LD", &[IndirectI, Register(x)]) => Instruction::DumpReg(x),
+        ("LD", &[Register(x), IndirectI]) => Instruction::LoadReg(x),
+        ("LD", &[Flags, Register(x)]) => Instruction::StoreFlags(x),
+        ("LD", &[Register(x), Flags]) => Instruction::LoadFlags(x),
+        ("ADD", &[Register(x), Value(kk)]) => Instruction::AddConst(x, byte(kk)?),
+        ("ADD", &[Register(x), Register(y)]) => Instruction::Add(x, y),
+        ("ADD", &[I, Register(x)]) => Instruction::AddOffset(x),
+        ("OR", &[Register(x), Register(y)]) => Instruction::SetOr(x, y),
+        ("AND", &[Register(x), Register(y)]) => Instruction::SetAnd(x, y),
+        ("XOR", &[Register(x), Register(y)]) => Instruction::SetXor(x, y),
+        ("SUB", &[Register(x), Register(y)]) => Instruction::Subtract(x, y),
+        ("SHR", &[Register(x), Register(y)]) => Instruction::ShiftRight(x, y),
+        ("SUBN", &[Register(x), Register(y)]) => Instruction::Reduce(x, y),
+        ("SHL", &[Register(x), Register(y)]) => Instruction::ShiftLeft(x, y),
+        ("RND", &[Register(x), Value(kk)]) => Instruction::BitwiseRandom(x, byte(kk)?),
+        ("DRW", &[Register(x), Register(y), Value(n)]) => {
+            Instruction::DrawSprite(x, y, nibble(n)?)
+        }
+        ("SKP", &[Register(x)]) => Instruction::SkipIfPressed(x),
+        ("SKNP", &[Register(x)]) => Instruction::SkipIfNotPressed(x),
+        ("PLANE", &[Value(n)]) => Instruction::SelectPlane(nibble(n)?),
+        ("AUDIO", &[]) => Instruction::LoadAudioPattern,
+        ("PITCH", &[Register(x)]) => Instruction::SetPitch(x),
+        _ => {
+            return Err(format_err!("\"{} {}\" is not a valid instruction",
+                                   mnemonic,
+                                   statement.operands.join(", ")))
+        }
+    };
+
+    let opcode = instruction.to_u16();
+    Ok(vec![(opcode >> 8) as u8, opcode as u8])
+}
@@ -0,0 +1,189 @@
+use std::f32::consts::PI;
+use std::io::Write;
+use std::str::FromStr;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use {Error, TIMER_FREQUENCY};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl FromStr for Waveform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Waveform, Error> {
+        match s.to_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" | "saw" => Ok(Waveform::Sawtooth),
+            "sine" => Ok(Waveform::Sine),
+            _ => Err(format_err!("Unknown waveform \"{}\"", s)),
+        }
+    }
+}
+
+/// What the beeper sounds like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// Pitch in Hz.
+    pub frequency: f32,
+    /// Peak amplitude between 0.0 and 1.0.
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Tone {
+        Tone {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// An XO-CHIP sample: 128 one bit samples, most significant bit first,
+/// played in a loop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pattern {
+    pub bits: [u8; 16],
+    pub pitch: u8,
+}
+
+impl Pattern {
+    /// Bits played per second, 4000 at the default pitch of 64 and doubling
+    /// every 48 steps.
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    fn bit(&self, idx: usize) -> bool {
+        self.bits[idx / 8] & (0x80 >> (idx % 8)) != 0
+    }
+}
+
+/// Produces the samples of a `Tone`, keeping track of the phase so that
+/// consecutive buffers join up without clicks.
+pub struct ToneGenerator {
+    pub tone: Tone,
+    /// Played instead of the tone's waveform when set, at the tone's volume.
+    pub pattern: Option<Pattern>,
+    sample_rate: u32,
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(tone: Tone, sample_rate: u32) -> ToneGenerator {
+        ToneGenerator {
+            tone,
+            pattern: None,
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if let Some(pattern) = self.pattern {
+            let bit = pattern.bit((self.phase * 128.0) as usize % 128);
+            self.phase = (self.phase + pattern.rate() / 128.0 / self.sample_rate as f32) % 1.0;
+            return if bit { self.tone.volume } else { -self.tone.volume };
+        }
+
+        let value = match self.tone.waveform {
+            Waveform::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+        };
+
+        self.phase = (self.phase + self.tone.frequency / self.sample_rate as f32) % 1.0;
+        value * self.tone.volume
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+}
+
+/// Implemented by anything that can sound the beeper.
+pub trait Audio {
+    /// Called once per 60 Hz frame with whether the sound timer is running,
+    /// and the XO-CHIP pattern to play in place of the tone, if any.
+    fn update(&mut self, playing: bool, pattern: Option<Pattern>) -> Result<(), Error>;
+}
+
+/// Renders the beeper into memory instead of a sound card, so runs without
+/// any audio hardware can still check or keep what would have been heard.
+pub struct BufferAudio {
+    pub samples: Vec<f32>,
+    generator: ToneGenerator,
+    sample_rate: u32,
+    remainder: u32,
+}
+
+impl BufferAudio {
+    pub fn new(tone: Tone, sample_rate: u32) -> BufferAudio {
+        BufferAudio {
+            samples: vec![],
+            generator: ToneGenerator::new(tone, sample_rate),
+            sample_rate,
+            remainder: 0,
+        }
+    }
+
+    /// Writes the samples rendered so far as a 16-bit mono PCM WAV file.
+    pub fn write_wav<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let data_len = self.samples.len() as u32 * 2;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_u32::<LittleEndian>(36 + data_len)?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_u32::<LittleEndian>(16)?;
+        writer.write_u16::<LittleEndian>(1)?;
+        writer.write_u16::<LittleEndian>(1)?;
+        writer.write_u32::<LittleEndian>(self.sample_rate)?;
+        writer.write_u32::<LittleEndian>(self.sample_rate * 2)?;
+        writer.write_u16::<LittleEndian>(2)?;
+        writer.write_u16::<LittleEndian>(16)?;
+
+        writer.write_all(b"data")?;
+        writer.write_u32::<LittleEndian>(data_len)?;
+        for sample in self.samples.iter() {
+            let clamped = sample.clamp(-1.0, 1.0);
+            writer.write_i16::<LittleEndian>((clamped * i16::MAX as f32) as i16)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Audio for BufferAudio {
+    fn update(&mut self, playing: bool, pattern: Option<Pattern>) -> Result<(), Error> {
+        self.generator.pattern = pattern;
+
+        // Carry the fraction of a sample left over from each frame so that a
+        // second of frames always produces exactly `sample_rate` samples
+        let total = self.sample_rate + self.remainder;
+        let count = total / TIMER_FREQUENCY;
+        self.remainder = total % TIMER_FREQUENCY;
+
+        for _ in 0..count {
+            let sample = if playing { self.generator.next_sample() } else { 0.0 };
+            self.samples.push(sample);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+extern crate chip8;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process::exit;
+
+use chip8::assembler::assemble_file;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        println!("Usage: chip8-assemble <SOURCE> <PROGRAM>");
+        exit(1);
+    }
+
+    let program = match assemble_file(&args[0]) {
+        Ok(program) => program,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = File::create(&args[1]).and_then(|mut file| file.write_all(&program)) {
+        println!("Error writing \"{}\": {}", args[1], e);
+        exit(1);
+    }
+}
@@ -0,0 +1,290 @@
+extern crate chip8;
+#[macro_use]
+extern crate failure;
+
+use std::convert::TryFrom;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+
+use failure::Error;
+use chip8::read_binary;
+use chip8::assembler::assemble;
+use chip8::cpu::CPU;
+use chip8::debugger::{Debugger, Stop};
+use chip8::emulator::Emulator;
+use chip8::instructions::Instruction;
+use chip8::platform::Platform;
+
+/// Instructions `continue`, `next` and `finish` run before giving up, so a
+/// program stuck in a loop hands control back eventually.
+const STEP_LIMIT: usize = 10_000_000;
+
+const HELP: &str = "\
+Commands:
+  step [N], s      execute N instructions (default 1)
+  next, n          step over a subroutine call
+  finish, f        run until the current subroutine returns
+  continue, c      run until a breakpoint, watchpoint or halt
+  break ADDR, b    set a breakpoint
+  delete ADDR      remove a breakpoint
+  watch ADDR|VX    stop when a memory address or register changes
+  unwatch ADDR|VX  remove a watchpoint
+  info             list breakpoints and watchpoints
+  regs, r          show the registers
+  mem ADDR [LEN]   dump memory (LEN defaults to 16), x for short
+  stack            show the call stack
+  patch ADDR INSTR assemble an instruction over the one at ADDR
+  press KEY        hold a keypad key down
+  release KEY      let a keypad key go
+  quit, q          leave the debugger
+Addresses and keys are in hex. An empty line repeats the last command.";
+
+enum Watch {
+    Memory(usize),
+    Register(u8),
+}
+
+fn parse_address(text: Option<&str>) -> Result<usize, Error> {
+    let text = text.ok_or_else(|| format_err!("Expected an address"))?;
+    let digits = text.trim_start_matches("0x");
+    usize::from_str_radix(digits, 16).map_err(|_| format_err!("\"{}\" is not an address", text))
+}
+
+fn parse_key(text: Option<&str>) -> Result<u8, Error> {
+    match text.map(|text| u8::from_str_radix(text, 16)) {
+        Some(Ok(key)) if key < 16 => Ok(key),
+        _ => Err(format_err!("Expected a keypad key between 0 and F")),
+    }
+}
+
+fn parse_watch(text: Option<&str>) -> Result<Watch, Error> {
+    if let Some(text) = text {
+        if text.len() == 2 && (text.starts_with('v') || text.starts_with('V')) {
+            if let Ok(register) = u8::from_str_radix(&text[1..], 16) {
+                return Ok(Watch::Register(register));
+            }
+        }
+    }
+
+    parse_address(text).map(Watch::Memory)
+}
+
+fn show_position(debugger: &Debugger) {
+    match debugger.current_instruction() {
+        Ok(Some(instruction)) => println!("{:04x}: {}", debugger.emulator.cpu.pc, instruction),
+        Ok(None) => println!("{:04x}: unknown opcode", debugger.emulator.cpu.pc),
+        Err(e) => println!("{:04x}: {}", debugger.emulator.cpu.pc, e),
+    }
+}
+
+fn report(debugger: &Debugger, stop: Result<Stop, Error>) {
+    match stop {
+        Ok(Stop::Stepped) => (),
+        Ok(stop) => println!("{}", stop),
+        Err(e) => println!("Error: {}", e),
+    }
+    show_position(debugger);
+}
+
+/// Runs one command, returning false when the debugger should exit.
+fn execute(debugger: &mut Debugger, line: &str) -> Result<bool, Error> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some(command) => command,
+        None => return Ok(true),
+    };
+    let argument = words.next();
+
+    match command {
+        "step" | "s" => {
+            let count = match argument {
+                Some(count) => count.parse().map_err(|_| format_err!("Expected a count"))?,
+                None => 1,
+            };
+            let mut stop = Ok(Stop::Stepped);
+            for _ in 0..count {
+                stop = debugger.step();
+                match stop {
+                    Ok(Stop::Stepped) => (),
+                    _ => break,
+                }
+            }
+            report(debugger, stop);
+        }
+        "next" | "n" => {
+            let stop = debugger.step_over(STEP_LIMIT);
+            report(debugger, stop);
+        }
+        "finish" | "f" => {
+            let stop = debugger.step_out(STEP_LIMIT);
+            report(debugger, stop);
+        }
+        "continue" | "c" => {
+            let stop = debugger.run(STEP_LIMIT);
+            report(debugger, stop);
+        }
+        "break" | "b" => {
+            let address = parse_address(argument)?;
+            debugger.breakpoints.insert(address);
+            println!("Breakpoint at {:#05x}", address);
+        }
+        "delete" => {
+            let address = parse_address(argument)?;
+            if !debugger.breakpoints.remove(&address) {
+                println!("No breakpoint at {:#05x}", address);
+            }
+        }
+        "watch" => {
+            match parse_watch(argument)? {
+                Watch::Memory(address) => {
+                    debugger.memory_watchpoints.insert(address);
+                }
+                Watch::Register(register) => {
+                    debugger.register_watchpoints.insert(register);
+                }
+            }
+        }
+        "unwatch" => {
+            let removed = match parse_watch(argument)? {
+                Watch::Memory(address) => debugger.memory_watchpoints.remove(&address),
+                Watch::Register(register) => debugger.register_watchpoints.remove(&register),
+            };
+            if !removed {
+                println!("No such watchpoint");
+            }
+        }
+        "info" => {
+            for address in debugger.breakpoints.iter() {
+                println!("Breakpoint at {:#05x}", address);
+            }
+            for address in debugger.memory_watchpoints.iter() {
+                println!("Watching memory {:#05x}", address);
+            }
+            for register in debugger.register_watchpoints.iter() {
+                println!("Watching V{:X}", register);
+            }
+        }
+        "regs" | "r" => println!("{}", debugger.registers()),
+        "mem" | "x" => {
+            let address = parse_address(argument)?;
+            let length = match words.next() {
+                Some(length) => length.parse().map_err(|_| format_err!("Expected a length"))?,
+                None => 16,
+            };
+            println!("{}", debugger.dump_memory(address, length)?);
+        }
+        "stack" => println!("{}", debugger.stack_trace()),
+        "patch" => {
+            let address = parse_address(argument)?;
+            let source: Vec<&str> = words.collect();
+            let program = assemble(&source.join(" "))?;
+            let opcode = match program.as_slice() {
+                &[high, low] => (high as u16) << 8 | low as u16,
+                _ => return Err(format_err!("Expected a single two byte instruction")),
+            };
+            let instruction = Instruction::try_from(opcode)?;
+            debugger.emulator.cpu.memory.patch(address, &instruction)?;
+        }
+        "press" => debugger.emulator.cpu.keys.press(parse_key(argument)?),
+        "release" => debugger.emulator.cpu.keys.release(parse_key(argument)?),
+        "help" | "h" => println!("{}", HELP),
+        "quit" | "q" => return Ok(false),
+        _ => println!("Unknown command \"{}\", try \"help\"", command),
+    }
+
+    Ok(true)
+}
+
+fn main() {
+    let mut filename = None;
+    let mut platform = Platform::Chip8;
+    let mut quirks = None;
+    let mut seed = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                platform = match args.next().map(|name| name.parse()) {
+                    Some(Ok(platform)) => platform,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--platform expects one of chip8, schip or xochip");
+                        exit(1);
+                    }
+                }
+            }
+            "--quirks" => {
+                quirks = match args.next().map(|name| name.parse()) {
+                    Some(Ok(quirks)) => Some(quirks),
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--quirks expects one of vip, chip48, schip or xochip");
+                        exit(1);
+                    }
+                }
+            }
+            "--seed" => {
+                seed = match args.next().map(|value| value.parse()) {
+                    Some(Ok(seed)) => Some(seed),
+                    _ => {
+                        println!("--seed expects a number to seed random numbers with");
+                        exit(1);
+                    }
+                }
+            }
+            _ => filename = Some(arg),
+        }
+    }
+    let filename = filename.expect("filename?");
+
+    let data = match read_binary(&filename) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Error reading binary \"{}\": {}", filename, e);
+            exit(1);
+        }
+    };
+
+    let quirks = quirks.unwrap_or(platform.quirks());
+    let mut cpu = match CPU::new(&data, platform, quirks) {
+        Ok(cpu) => cpu,
+        Err(e) => {
+            println!("Failed to load \"{}\": {}", filename, e);
+            exit(1);
+        }
+    };
+    if let Some(seed) = seed {
+        cpu = cpu.with_seed(seed);
+    }
+    let mut debugger = Debugger::new(Emulator::new(cpu));
+    show_position(&debugger);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut last = String::new();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        if !line.trim().is_empty() {
+            last = line;
+        }
+
+        match execute(&mut debugger, &last) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+extern crate chip8;
+
+use std::env;
+use std::process::exit;
+
+use chip8::read_binary;
+use chip8::disassembler::Disassembly;
+
+fn main() {
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => {
+            println!("Usage: chip8-disassemble <PROGRAM>");
+            exit(1);
+        }
+    };
+
+    let data = match read_binary(&filename) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Error reading binary \"{}\": {}", filename, e);
+            exit(1);
+        }
+    };
+
+    print!("{}", Disassembly::new(&data));
+}
@@ -0,0 +1,121 @@
+extern crate chip8;
+
+use std::env;
+use std::process::exit;
+
+use chip8::read_binary;
+use chip8::display::TextRenderer;
+use chip8::movie::Movie;
+use chip8::screenshot::{CaptureOptions, Screenshot};
+use chip8::video::VideoRecorder;
+
+const USAGE: &str = "Usage: chip8-replay <PROGRAM> <MOVIE> [--screenshot FILE] \
+                             [--video FILE] [--scale N] [--palette COLOURS]";
+
+fn main() {
+    let mut files = vec![];
+    let mut screenshot_path = None;
+    let mut capture = CaptureOptions::new(1);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--screenshot" => {
+                screenshot_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        println!("--screenshot expects the path of a .png, .pbm or .pgm file");
+                        exit(1);
+                    }
+                }
+            }
+            _ => {
+                match capture.parse_arg(&arg, &mut args) {
+                    Ok(true) => {}
+                    Ok(false) => files.push(arg),
+                    Err(e) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                }
+            }
+        }
+    }
+    if files.len() != 2 {
+        println!("{}", USAGE);
+        exit(1);
+    }
+    let CaptureOptions { scale, palette, video_path } = capture;
+
+    let data = match read_binary(&files[0]) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Error reading binary \"{}\": {}", files[0], e);
+            exit(1);
+        }
+    };
+
+    let movie = match Movie::load_file(&files[1]) {
+        Ok(movie) => movie,
+        Err(e) => {
+            println!("Failed to load movie \"{}\": {}", files[1], e);
+            exit(1);
+        }
+    };
+
+    let mut emulator = match movie.start(&data) {
+        Ok(emulator) => emulator,
+        Err(e) => {
+            println!("Failed to replay movie: {}", e);
+            exit(1);
+        }
+    };
+
+    let mut video = match video_path {
+        Some(ref path) => {
+            match VideoRecorder::create(path, scale, palette) {
+                Ok(recorder) => Some(recorder),
+                Err(e) => {
+                    println!("Failed to record video \"{}\": {}", path, e);
+                    exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    for frame in movie.frames.iter() {
+        emulator.cpu.keys = frame.clone();
+        if let Err(e) = emulator.run_frame() {
+            println!("Replay stopped: {}", e);
+            exit(1);
+        }
+
+        if let Some(ref mut recorder) = video {
+            if let Err(e) = recorder.capture(&emulator.cpu) {
+                println!("Failed to record video: {}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(ref mut recorder) = video {
+        if let Err(e) = recorder.finish() {
+            println!("Failed to save video: {}", e);
+            exit(1);
+        }
+    }
+
+    match screenshot_path {
+        Some(path) => {
+            if let Err(e) = Screenshot::of(&emulator.cpu, scale, palette).save(&path) {
+                println!("Failed to save screenshot \"{}\": {}", path, e);
+                exit(1);
+            }
+        }
+        None => {
+            let mut screen = TextRenderer::default();
+            emulator.cpu.show(&mut screen).unwrap();
+            print!("{}", screen.text);
+        }
+    }
+}
@@ -1,98 +1,239 @@
-use std::io::{Cursor, Error, Write};
-
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-
-use byteorder::{BigEndian, ReadBytesExt};
-use rand;
-
+use display::Renderer;
+use error::CpuError;
 use instructions::Instruction;
-use FONT4X5;
-
-const GRID_WIDTH: usize = 64;
-const GRID_HEIGHT: usize = 32;
+use keypad::Keypad;
+use memory::{Memory, PROGRAM_START};
+use platform::Platform;
+use quirks::Quirks;
+use rng::Rng;
+use trace::{TraceEntry, Tracer};
+use {FONT4X5, FONT8X10};
+
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+const BIG_FONT_ADDRESS: usize = 0x50;
+/// First word of XO-CHIP's four byte `LD I, LONG` instruction.
+pub const LONG_ADDRESS_OPCODE: u16 = 0xF000;
 
 pub struct CPU {
     pub regs: [u8; 16],
     pub address: u16,
     pub stack: Vec<usize>,
-    pub memory: Cursor<Vec<u8>>,
+    pub stack_depth: usize,
+    pub memory: Memory,
 
     pub delay_timer: u8,
     pub sound_timer: u8,
 
     pub pc: usize,
 
-    pub keys: [u8; 16],
-    pub display: Option<Canvas<Window>>,
+    pub keys: Keypad,
+    pub waiting_for_key: bool,
     pub grid: Vec<u8>,
-}
+    pub hires: bool,
+    pub flags: [u8; 16],
+    pub halted: bool,
 
-impl CPU {
-    pub fn new(data: &Vec<u8>, display: Option<Canvas<Window>>) -> CPU {
-        let mut memory = vec![0; 4096];
-        for i in 0..data.len() {
-            memory[0x200 + i] = data[i];
-        }
+    pub platform: Platform,
+    pub planes: u8,
+    pub audio_pattern: [u8; 16],
+    pub pitch: u8,
 
-        for i in 0..FONT4X5.len() {
-            memory[0x0 + i] = FONT4X5[i];
-        }
+    pub quirks: Quirks,
+    pub vblank_wait: bool,
+    pub rng: Rng,
 
-        CPU {
+    /// Records every instruction executed, when set.
+    pub tracer: Option<Tracer>,
+}
+
+impl CPU {
+    /// Fails if the program doesn't fit in the platform's memory.
+    pub fn new(data: &Vec<u8>, platform: Platform, quirks: Quirks) -> Result<CPU, ::Error> {
+        let mut memory = Memory::new(platform.memory_size());
+        memory.load(PROGRAM_START, data)?;
+        memory.load(0x0, &FONT4X5).unwrap();
+        memory.load(BIG_FONT_ADDRESS, &FONT8X10).unwrap();
+
+        Ok(CPU {
             regs: [0; 16],
             address: 0,
             stack: vec![],
-            memory: Cursor::new(memory),
+            stack_depth: platform.stack_depth(),
+            memory,
             delay_timer: 0,
             sound_timer: 0,
-            pc: 0x200,
-            keys: [0; 16],
-            display: display,
-            grid: vec![0; GRID_WIDTH * GRID_HEIGHT],
+            pc: PROGRAM_START,
+            keys: Keypad::new(),
+            waiting_for_key: false,
+            grid: vec![0; LORES_WIDTH * LORES_HEIGHT],
+            hires: false,
+            flags: [0; 16],
+            halted: false,
+            platform,
+            planes: 1,
+            audio_pattern: [0; 16],
+            pitch: 64,
+            quirks,
+            vblank_wait: false,
+            rng: Rng::from_entropy(),
+            tracer: None,
+        })
+    }
+
+    /// Seeds the random number generator, so that the program produces the
+    /// same random numbers on every run.
+    pub fn with_seed(mut self, seed: u64) -> CPU {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn show<R: Renderer>(&self, renderer: &mut R) -> Result<(), ::Error> {
+        renderer.render(&self.grid, self.width(), self.height())
+    }
+
+    /// Signals the start of a new frame, releasing a draw held by the
+    /// display wait quirk.
+    pub fn vblank(&mut self) {
+        self.vblank_wait = false;
+    }
+
+    fn clear(&mut self) {
+        for idx in 0..self.grid.len() {
+            self.grid[idx] &= !self.planes;
+        }
+    }
+
+    fn set_resolution(&mut self, hires: bool) {
+        self.hires = hires;
+        self.grid = vec![0; self.width() * self.height()];
+    }
+
+    /// Moves the selected planes by the given number of pixels, leaving any
+    /// unselected planes where they are.
+    fn scroll(&mut self, columns: isize, rows: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let planes = self.planes;
+        let source = self.grid.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - columns, y - rows);
+                let moved = if from_x >= 0 && from_x < width && from_y >= 0 && from_y < height {
+                    source[(from_y * width + from_x) as usize] & planes
+                } else {
+                    0
+                };
+
+                let idx = (y * width + x) as usize;
+                self.grid[idx] = (self.grid[idx] & !planes) | moved;
+            }
         }
     }
 
-    pub fn show(&mut self) {
-        if let Some(ref mut canvas) = self.display {
-            canvas.set_draw_color(Color::RGB(0, 0, 0));
-            canvas.clear();
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-
-            for y in 0..GRID_HEIGHT {
-                for x in 0..GRID_WIDTH {
-                    if self.grid[(y * GRID_WIDTH) + x] == 1 {
-                        canvas
-                            .fill_rect(Rect::new((x as u8) as i32 * 10,
-                                                 (y as u8) as i32 * 10,
-                                                 10,
-                                                 10)).unwrap();
+    /// XORs the sprite at I onto every selected plane, with its top left
+    /// corner at (Vx, Vy) wrapped onto the screen. Pixels past the edges wrap
+    /// or are clipped depending on the `sprite_wrap` quirk. Sprites that are
+    /// 16 rows tall are 16 pixels wide, stored as two bytes per row.
+    ///
+    /// Returns the number of rows in which a lit pixel was turned off, in any
+    /// plane.
+    fn draw_sprite(&mut self, vx: u8, vy: u8, rows: usize) -> Result<usize, ::Error> {
+        let width = self.width();
+        let height = self.height();
+        let start_x = self.regs[vx as usize] as usize % width;
+        let start_y = self.regs[vy as usize] as usize % height;
+        let sprite_width = if rows == 16 { 16 } else { 8 };
+        let row_bytes = sprite_width / 8;
+
+        // Bit n is set when row n collided
+        let mut collided_rows = 0u16;
+
+        // Each selected plane takes its own copy of the sprite data, one
+        // after the other starting at I
+        let mut offset = self.address as usize;
+        for plane in 0..2 {
+            let plane = 1 << plane;
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for y in 0..rows {
+                let mut row = 0u16;
+                for byte in 0..row_bytes {
+                    row = (row << 8) | self.memory.read(offset + y * row_bytes + byte)? as u16;
+                }
+
+                let screen_y = start_y + y;
+                if screen_y >= height && !self.quirks.sprite_wrap {
+                    continue;
+                }
+                let screen_y = screen_y % height;
+
+                for x in 0..sprite_width {
+                    if (row >> (sprite_width - 1 - x)) & 1 == 0 {
+                        continue;
+                    }
+
+                    let screen_x = start_x + x;
+                    if screen_x >= width && !self.quirks.sprite_wrap {
+                        continue;
+                    }
+
+                    let idx = screen_y * width + screen_x % width;
+                    if self.grid[idx] & plane != 0 {
+                        collided_rows |= 1 << y;
                     }
+                    self.grid[idx] ^= plane;
                 }
             }
 
-            canvas.present();
+            offset += rows * row_bytes;
         }
+
+        Ok(collided_rows.count_ones() as usize)
     }
 
-    fn clear(&mut self) {
-        for idx in 0..GRID_WIDTH * GRID_HEIGHT {
-            self.grid[idx] = 0;
+    /// Advances past the next instruction, which takes four bytes when it's
+    /// the long address load. Like the other extended instructions, that's
+    /// recognised whatever the platform.
+    fn skip(&mut self) {
+        self.inc_pc();
+        if let Ok(LONG_ADDRESS_OPCODE) = self.fetch_opcode() {
+            self.inc_pc();
         }
     }
 
     fn inc_pc(&mut self) {
-        self.pc += 2;
+        self.pc = self.memory.wrap(self.pc + 2);
+    }
+
+    /// Points I at `address`, wrapped like every other address so it never
+    /// strays past the end of memory.
+    fn set_address(&mut self, address: usize) {
+        self.address = self.memory.wrap(address) as u16;
     }
 
-    pub fn fetch_opcode(&mut self) -> Result<u16, Error> {
-        self.memory.set_position(self.pc as u64);
-        self.memory.read_u16::<BigEndian>()
+    pub fn fetch_opcode(&mut self) -> Result<u16, ::Error> {
+        self.memory.read_u16(self.pc)
     }
 
-    fn timer_tick(&mut self) {
+    /// Counts the delay and sound timers down by one. Both run at 60 Hz of
+    /// emulated time, so this should be called once per frame rather than
+    /// once per instruction.
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -102,9 +243,47 @@ impl CPU {
         }
     }
 
-    pub fn do_instruction(&mut self, instruction: &Instruction) -> Result<(), Error> {
+    /// Fetches, decodes and executes the instruction at `pc`.
+    pub fn step(&mut self) -> Result<(), ::Error> {
+        let opcode = self.fetch_opcode()?;
+        match Instruction::from_u16(&opcode) {
+            Some(instruction) => self.do_instruction(&instruction)?,
+            None => {
+                return Err(CpuError::UnknownOpcode {
+                        opcode,
+                        pc: self.pc,
+                    }
+                    .into())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a decoded instruction, recording it with the tracer if there is
+    /// one and its filters let it through.
+    pub fn do_instruction(&mut self, instruction: &Instruction) -> Result<(), ::Error> {
+        let traced = match self.tracer {
+            Some(ref tracer) => tracer.wants(self.pc, instruction),
+            None => false,
+        };
+        if !traced {
+            return self.execute(instruction);
+        }
+
+        let (pc, regs, address) = (self.pc, self.regs, self.address);
+        self.execute(instruction)?;
+
+        let entry = TraceEntry::new(pc, instruction.to_u16(), &regs, address, self);
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.record(&entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), ::Error> {
         let mut should_increment = true;
-        self.timer_tick();
 
         match instruction {
             Instruction::ClearDisplay => {
@@ -112,10 +291,39 @@ impl CPU {
             }
 
             Instruction::Return => {
-                if let Some(ret) = self.stack.pop() {
-                    self.pc = ret as usize;
-                    should_increment = true;
-                };
+                match self.stack.pop() {
+                    Some(ret) => self.pc = ret,
+                    None => return Err(CpuError::StackUnderflow { pc: self.pc }.into()),
+                }
+            }
+
+            Instruction::ScrollDown(rows) => {
+                self.scroll(0, *rows as isize);
+            }
+
+            Instruction::ScrollUp(rows) => {
+                self.scroll(0, -(*rows as isize));
+            }
+
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+            }
+
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+            }
+
+            Instruction::Exit => {
+                self.halted = true;
+                should_increment = false;
+            }
+
+            Instruction::LowRes => {
+                self.set_resolution(false);
+            }
+
+            Instruction::HighRes => {
+                self.set_resolution(true);
             }
 
             Instruction::JumpToAddress(address) => {
@@ -124,6 +332,14 @@ impl CPU {
             }
 
             Instruction::CallSubroutine(address) => {
+                if self.stack.len() >= self.stack_depth {
+                    return Err(CpuError::StackOverflow {
+                            pc: self.pc,
+                            trace: self.stack.clone(),
+                        }
+                        .into());
+                }
+
                 self.stack.push(self.pc);
                 self.pc = *address as usize;
                 should_increment = false;
@@ -131,19 +347,31 @@ impl CPU {
 
             Instruction::SkipIfEqual(vx, value) => {
                 if self.regs[*vx as usize] == *value {
-                    self.inc_pc();
+                    self.skip();
                 }
             }
 
             Instruction::SkipIfNotEqual(vx, value) => {
                 if self.regs[*vx as usize] != *value {
-                    self.inc_pc();
+                    self.skip();
                 }
             }
 
             Instruction::SkipIfEqualRegister(vx, vy) => {
                 if self.regs[*vx as usize] == self.regs[*vy as usize] {
-                    self.inc_pc();
+                    self.skip();
+                }
+            }
+
+            Instruction::SaveRange(vx, vy) => {
+                for (offset, idx) in register_range(*vx, *vy).into_iter().enumerate() {
+                    self.memory.write(self.address as usize + offset, self.regs[idx])?;
+                }
+            }
+
+            Instruction::LoadRange(vx, vy) => {
+                for (offset, idx) in register_range(*vx, *vy).into_iter().enumerate() {
+                    self.regs[idx] = self.memory.read(self.address as usize + offset)?;
                 }
             }
 
@@ -162,54 +390,69 @@ impl CPU {
 
             Instruction::SetOr(vx, vy) => {
                 self.regs[*vx as usize] |= self.regs[*vy as usize];
+                if self.quirks.vf_reset {
+                    self.regs[0xf] = 0;
+                }
             }
 
             Instruction::SetAnd(vx, vy) => {
                 self.regs[*vx as usize] &= self.regs[*vy as usize];
+                if self.quirks.vf_reset {
+                    self.regs[0xf] = 0;
+                }
             }
 
             Instruction::SetXor(vx, vy) => {
                 self.regs[*vx as usize] ^= self.regs[*vy as usize];
+                if self.quirks.vf_reset {
+                    self.regs[0xf] = 0;
+                }
             }
 
+            // The arithmetic group writes Vx before VF, so when x is F the
+            // flag is what's left in it. No borrow means VF is 1, which
+            // includes equal operands.
             Instruction::Add(vx, vy) => {
                 let x = self.regs[*vx as usize];
                 let y = self.regs[*vy as usize];
-
-                let ret = x as u16 + y as u16;
-                self.regs[*vx as usize] = x.wrapping_add(y);
-                self.regs[0xF] = (ret > 255) as u8;
+                let (result, carry) = x.overflowing_add(y);
+                self.regs[*vx as usize] = result;
+                self.regs[0xF] = carry as u8;
             }
 
             Instruction::Subtract(vx, vy) => {
                 let x = self.regs[*vx as usize];
                 let y = self.regs[*vy as usize];
-
-                self.regs[0xF] = (x > y) as u8;
-                self.regs[*vx as usize] = x.wrapping_sub(y);
+                let (result, borrow) = x.overflowing_sub(y);
+                self.regs[*vx as usize] = result;
+                self.regs[0xF] = !borrow as u8;
             }
 
             Instruction::ShiftRight(vx, vy) => {
-                self.regs[0xF] = self.regs[*vy as usize] & 1;
-                self.regs[*vx as usize] = self.regs[*vy as usize] >> 1;
+                let source = if self.quirks.shift_uses_vy { *vy } else { *vx };
+                let value = self.regs[source as usize];
+                self.regs[*vx as usize] = value >> 1;
+                self.regs[0xF] = value & 1;
             }
 
             Instruction::Reduce(vx, vy) => {
                 let x = self.regs[*vx as usize];
                 let y = self.regs[*vy as usize];
-
-                self.regs[*vx as usize] = y.wrapping_sub(x);
-                self.regs[0xF] = (y > x) as u8;
+                let (result, borrow) = y.overflowing_sub(x);
+                self.regs[*vx as usize] = result;
+                self.regs[0xF] = !borrow as u8;
             }
 
             Instruction::ShiftLeft(vx, vy) => {
-                self.regs[0xF] = self.regs[*vy as usize] >> 7;
-                self.regs[*vx as usize] = self.regs[*vy as usize] << 1;
+                let source = if self.quirks.shift_uses_vy { *vy } else { *vx };
+                let value = self.regs[source as usize];
+                self.regs[*vx as usize] = value << 1;
+                self.regs[0xF] = value >> 7;
             }
 
             Instruction::SkipIfNotEqualRegister(vx, vy) => {
                 if self.regs[*vx as usize] != self.regs[*vy as usize] {
-                    self.inc_pc();
+                    self.skip();
                 }
             }
 
@@ -217,55 +460,53 @@ impl CPU {
                 self.address = *address;
             }
 
+            Instruction::SetLongMemoryAddress => {
+                let address = self.memory.read_u16(self.pc + 2)?;
+                self.set_address(address as usize);
+                self.inc_pc();
+            }
+
             Instruction::JumpToV0Address(address) => {
-                self.pc = (*address + self.regs[0] as u16) as usize;
+                let reg = if self.quirks.jump_uses_vx { (*address >> 8) as usize } else { 0 };
+                self.pc = self.memory.wrap(*address as usize + self.regs[reg] as usize);
                 should_increment = false;
             }
 
             Instruction::BitwiseRandom(vx, value) => {
-                self.regs[*vx as usize] = rand::random::<u8>() & *value;
+                self.regs[*vx as usize] = self.rng.next_u8() & *value;
             }
 
             Instruction::DrawSprite(vx, vy, height) => {
-                let mut start_x = self.regs[*vx as usize];
-                let mut start_y = self.regs[*vy as usize];
-                let height = *height;
-                self.regs[0xf] = 0;
-
-                if start_x > (GRID_WIDTH - 1) as u8 {
-                    start_x = 0;
-                }
-
-                for y in 0..height {
-                    self.memory.set_position((self.address + y as u16) as u64);
-                    let row = self.memory.read_u8()?;
-
-                    let final_y = y as u16 + start_y as u16;
-                    for x in 0..8 {
-                        let final_x = x as u16 + start_x as u16;
-                        if final_x > (GRID_WIDTH - 1) as u16 {
-                            continue;
-                        }
+                let start_y = self.regs[*vy as usize] as usize % self.height();
+                let rows = if *height == 0 { 16 } else { *height as usize };
+                let collided = self.draw_sprite(*vx, *vy, rows)?;
+
+                self.regs[0xf] = if self.quirks.collision_count && self.hires {
+                    let clipped = if self.quirks.sprite_wrap {
+                        0
+                    } else {
+                        (start_y + rows).saturating_sub(self.height())
+                    };
+                    (collided + clipped) as u8
+                } else {
+                    (collided > 0) as u8
+                };
 
-                        let grid_pos = ((final_y * GRID_WIDTH as u16) + final_x) as usize;
-                        if (row >> 7 - x) & 1 != 0 {
-                            self.regs[0xf] = (self.grid[grid_pos] == 1) as u8;
-                            self.grid[grid_pos] ^= 1;
-                        }
-                    }
+                if self.quirks.display_wait {
+                    self.vblank_wait = true;
                 }
             }
 
             Instruction::SkipIfPressed(vx) => {
-                let key = self.regs[*vx as usize] as usize;
-                if self.keys[key] == 1 {
-                    self.inc_pc();
+                let key = self.regs[*vx as usize];
+                if self.keys.is_pressed(key) {
+                    self.skip();
                 }
             }
             Instruction::SkipIfNotPressed(vx) => {
-                let key = self.regs[*vx as usize] as usize;
-                if self.keys[key] != 1 {
-                    self.inc_pc();
+                let key = self.regs[*vx as usize];
+                if !self.keys.is_pressed(key) {
+                    self.skip();
                 }
             }
 
@@ -274,9 +515,26 @@ impl CPU {
             }
 
             Instruction::WaitForPress(vx) => {
-                let key = self.regs[*vx as usize] as usize;
-                if self.keys[key] != 1 {
-                    should_increment = false;
+                let key = if self.quirks.wait_for_release {
+                    self.keys.released_key()
+                } else {
+                    self.keys.pressed_key()
+                };
+
+                // The first time through only starts listening for a key, so
+                // that anything pressed before the wait doesn't count
+                match key {
+                    Some(key) if self.waiting_for_key => {
+                        self.regs[*vx as usize] = key;
+                        self.waiting_for_key = false;
+                    }
+                    _ => {
+                        if !self.waiting_for_key {
+                            self.keys.clear_edges();
+                            self.waiting_for_key = true;
+                        }
+                        should_increment = false;
+                    }
                 }
             }
 
@@ -289,11 +547,16 @@ impl CPU {
             }
 
             Instruction::AddOffset(vx) => {
-                self.address += self.regs[*vx as usize] as u16;
+                let address = self.address as usize + self.regs[*vx as usize] as usize;
+                self.set_address(address);
             }
 
             Instruction::SetMemoryForFont(vx) => {
-                self.address = (self.regs[*vx as usize] * 5) as u16;
+                self.address = (self.regs[*vx as usize] & 0xF) as u16 * 5;
+            }
+
+            Instruction::SetMemoryForBigFont(vx) => {
+                self.address = (BIG_FONT_ADDRESS + self.regs[*vx as usize] as usize * 10) as u16;
             }
 
             Instruction::SetBCD(vx) => {
@@ -302,25 +565,61 @@ impl CPU {
                 let h = val / 100;
                 let t = (val / 10) % 10;
                 let d = (val % 100) % 10;
-                self.memory.set_position(self.address as u64);
-                self.memory.write(&[h, t, d])?;
+                let address = self.address as usize;
+                self.memory.write(address, h)?;
+                self.memory.write(address + 1, t)?;
+                self.memory.write(address + 2, d)?;
             }
 
             Instruction::DumpReg(vx) => {
                 for idx in 0..*vx + 1 {
-                    self.memory.set_position(self.address as u64);
-                    self.memory.write(&[self.regs[idx as usize]])?;
-                    self.address += 1;
+                    let address = self.address as usize + idx as usize;
+                    self.memory.write(address, self.regs[idx as usize])?;
+                }
+
+                if self.quirks.load_store_increments_i {
+                    let address = self.address as usize + *vx as usize + 1;
+                    self.set_address(address);
                 }
             }
 
             Instruction::LoadReg(vx) => {
                 for idx in 0..*vx + 1 {
-                    self.memory.set_position((self.address as u16) as u64);
-                    self.regs[idx as usize] = self.memory.read_u8()?;
-                    self.address += 1;
+                    let address = self.address as usize + idx as usize;
+                    self.regs[idx as usize] = self.memory.read(address)?;
+                }
+
+                if self.quirks.load_store_increments_i {
+                    let address = self.address as usize + *vx as usize + 1;
+                    self.set_address(address);
                 }
             }
+
+            Instruction::StoreFlags(vx) => {
+                for idx in 0..*vx as usize + 1 {
+                    self.flags[idx] = self.regs[idx];
+                }
+            }
+
+            Instruction::LoadFlags(vx) => {
+                for idx in 0..*vx as usize + 1 {
+                    self.regs[idx] = self.flags[idx];
+                }
+            }
+
+            Instruction::SelectPlane(planes) => {
+                self.planes = *planes & 0x3;
+            }
+
+            Instruction::LoadAudioPattern => {
+                for idx in 0..self.audio_pattern.len() {
+                    self.audio_pattern[idx] = self.memory.read(self.address as usize + idx)?;
+                }
+            }
+
+            Instruction::SetPitch(vx) => {
+                self.pitch = self.regs[*vx as usize];
+            }
         }
 
         if should_increment {
@@ -330,3 +629,14 @@ impl CPU {
         Ok(())
     }
 }
+
+/// Registers covered by an XO-CHIP range save/load, walking backwards when
+/// the first register is the higher of the two.
+fn register_range(vx: u8, vy: u8) -> Vec<usize> {
+    let (x, y) = (vx as usize, vy as usize);
+    if x <= y {
+        (x..y + 1).collect()
+    } else {
+        (y..x + 1).rev().collect()
+    }
+}
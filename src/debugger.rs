@@ -0,0 +1,216 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use cpu::CPU;
+use emulator::Emulator;
+use instructions::Instruction;
+use Error;
+
+/// Why the debugger handed control back.
+#[derive(Debug, PartialEq)]
+pub enum Stop {
+    /// The requested steps were completed.
+    Stepped,
+    Breakpoint(usize),
+    MemoryWatch { address: usize, old: u8, new: u8 },
+    RegisterWatch { register: u8, old: u8, new: u8 },
+    /// The program executed `00FD` and can't run any further.
+    Halted,
+    /// The step limit given to `run` ran out first.
+    StepLimit,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stop::Stepped => write!(f, "Stepped"),
+            Stop::Breakpoint(pc) => write!(f, "Breakpoint at {:#05x}", pc),
+            Stop::MemoryWatch { address, old, new } => {
+                write!(f, "Memory {:#05x} changed from {:#04x} to {:#04x}", address, old, new)
+            }
+            Stop::RegisterWatch { register, old, new } => {
+                write!(f, "V{:X} changed from {:#04x} to {:#04x}", register, old, new)
+            }
+            Stop::Halted => write!(f, "Program halted"),
+            Stop::StepLimit => write!(f, "Step limit reached"),
+        }
+    }
+}
+
+/// Runs an emulator under control an instruction at a time, stopping on
+/// breakpoints and watchpoints.
+///
+/// Frames end and the timers tick exactly as they do in the frontend, so
+/// programs that pace themselves with the delay timer behave the same.
+pub struct Debugger {
+    pub emulator: Emulator,
+    pub breakpoints: BTreeSet<usize>,
+    pub memory_watchpoints: BTreeSet<usize>,
+    pub register_watchpoints: BTreeSet<u8>,
+    /// Instructions executed since the debugger was created.
+    pub cycles: u64,
+}
+
+impl Debugger {
+    pub fn new(emulator: Emulator) -> Debugger {
+        Debugger {
+            emulator,
+            breakpoints: BTreeSet::new(),
+            memory_watchpoints: BTreeSet::new(),
+            register_watchpoints: BTreeSet::new(),
+            cycles: 0,
+        }
+    }
+
+    /// The instruction at the program counter, if it decodes.
+    pub fn current_instruction(&self) -> Result<Option<Instruction>, Error> {
+        let cpu = &self.emulator.cpu;
+        let opcode = cpu.memory.read_u16(cpu.pc)?;
+        Ok(Instruction::from_u16(&opcode))
+    }
+
+    /// Executes a single instruction, reporting any watchpoint it triggered
+    /// or a breakpoint at the instruction that follows.
+    pub fn step(&mut self) -> Result<Stop, Error> {
+        if self.emulator.cpu.halted {
+            return Ok(Stop::Halted);
+        }
+
+        let regs = self.emulator.cpu.regs;
+        let mut watched = vec![];
+        for address in self.memory_watchpoints.iter() {
+            watched.push((*address, self.emulator.cpu.memory.read(*address)?));
+        }
+
+        self.emulator.step()?;
+        self.cycles += 1;
+
+        let cpu = &self.emulator.cpu;
+        for register in self.register_watchpoints.iter() {
+            let (old, new) = (regs[*register as usize], cpu.regs[*register as usize]);
+            if old != new {
+                return Ok(Stop::RegisterWatch {
+                    register: *register,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        for (address, old) in watched {
+            let new = cpu.memory.read(address)?;
+            if old != new {
+                return Ok(Stop::MemoryWatch {
+                    address,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        if cpu.halted {
+            return Ok(Stop::Halted);
+        }
+
+        if self.breakpoints.contains(&cpu.pc) {
+            return Ok(Stop::Breakpoint(cpu.pc));
+        }
+
+        Ok(Stop::Stepped)
+    }
+
+    /// Steps until something stops the program or `limit` instructions have
+    /// been executed.
+    pub fn run(&mut self, limit: usize) -> Result<Stop, Error> {
+        self.run_until(limit, |_| false)
+    }
+
+    /// Steps over the next instruction, running a subroutine it calls to
+    /// completion.
+    pub fn step_over(&mut self, limit: usize) -> Result<Stop, Error> {
+        match self.current_instruction()? {
+            Some(Instruction::CallSubroutine(_)) => {
+                let depth = self.emulator.cpu.stack.len();
+                self.run_until(limit, |cpu| cpu.stack.len() <= depth)
+            }
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until the current subroutine returns to its caller.
+    pub fn step_out(&mut self, limit: usize) -> Result<Stop, Error> {
+        let depth = self.emulator.cpu.stack.len();
+        if depth == 0 {
+            return Err(format_err!("Not inside a subroutine"));
+        }
+
+        self.run_until(limit, |cpu| cpu.stack.len() < depth)
+    }
+
+    fn run_until<F: Fn(&CPU) -> bool>(&mut self, limit: usize, done: F) -> Result<Stop, Error> {
+        for _ in 0..limit {
+            let stop = self.step()?;
+            if stop != Stop::Stepped || done(&self.emulator.cpu) {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::StepLimit)
+    }
+
+    /// The registers, index register, program counter and timers.
+    pub fn registers(&self) -> String {
+        let cpu = &self.emulator.cpu;
+        let regs: Vec<String> = cpu.regs
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| format!("V{:X}={:02x}", idx, value))
+            .collect();
+
+        format!("{}\n{}\nI={:04x} PC={:04x} DT={:02x} ST={:02x}",
+                regs[..8].join(" "),
+                regs[8..].join(" "),
+                cpu.address,
+                cpu.pc,
+                cpu.delay_timer,
+                cpu.sound_timer)
+    }
+
+    /// A hex dump of `length` bytes of memory from `address`, sixteen bytes
+    /// to a line, stopping at the end of memory.
+    pub fn dump_memory(&self, address: usize, length: usize) -> Result<String, Error> {
+        let size = self.emulator.cpu.memory.len();
+        if address >= size {
+            return Err(format_err!("Address {:#05x} is past the end of memory", address));
+        }
+        let last = address.checked_add(length).map_or(size, |last| last.min(size));
+
+        let mut lines = vec![];
+        for start in (address..last).step_by(16) {
+            let end = (start + 16).min(last);
+            let mut bytes = vec![];
+            for idx in start..end {
+                bytes.push(format!("{:02x}", self.emulator.cpu.memory.read(idx)?));
+            }
+            lines.push(format!("{:04x}: {}", start, bytes.join(" ")));
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// The addresses of the calls on the stack, innermost first.
+    pub fn stack_trace(&self) -> String {
+        let cpu = &self.emulator.cpu;
+        if cpu.stack.is_empty() {
+            return "Stack is empty".to_string();
+        }
+
+        let frames: Vec<String> = cpu.stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(depth, ret)| format!("#{} {:#05x}", depth, ret))
+            .collect();
+        frames.join("\n")
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use cpu::LONG_ADDRESS_OPCODE;
+use instructions::Instruction;
+use memory::PROGRAM_START;
+
+/// A program split into code and data.
+///
+/// Code is found by following every path the program can take from
+/// `PROGRAM_START`: jumps, calls and both outcomes of each skip. Anything
+/// that isn't reached that way, or doesn't decode, is treated as data.
+/// `JP V0, addr` is assumed to land on `addr` itself, which is where the
+/// jump tables it's used for usually start.
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    /// Addresses of the instructions that were reached.
+    pub code: BTreeSet<usize>,
+    /// Jump and call targets inside the program.
+    pub labels: BTreeSet<usize>,
+}
+
+impl<'a> Disassembly<'a> {
+    pub fn new(rom: &'a [u8]) -> Disassembly<'a> {
+        let mut disassembly = Disassembly {
+            rom,
+            code: BTreeSet::new(),
+            labels: BTreeSet::new(),
+        };
+
+        let mut pending = vec![PROGRAM_START];
+        while let Some(address) = pending.pop() {
+            if disassembly.code.contains(&address) {
+                continue;
+            }
+
+            let instruction = match disassembly.instruction_at(address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            disassembly.code.insert(address);
+
+            let next = address + disassembly.length_at(address);
+            match instruction {
+                Instruction::JumpToAddress(target) |
+                Instruction::JumpToV0Address(target) => {
+                    disassembly.add_label(target as usize);
+                    pending.push(target as usize);
+                }
+                Instruction::CallSubroutine(target) => {
+                    disassembly.add_label(target as usize);
+                    pending.push(target as usize);
+                    pending.push(next);
+                }
+                Instruction::Return | Instruction::Exit => (),
+                Instruction::SkipIfEqual(..) |
+                Instruction::SkipIfNotEqual(..) |
+                Instruction::SkipIfEqualRegister(..) |
+                Instruction::SkipIfNotEqualRegister(..) |
+                Instruction::SkipIfPressed(..) |
+                Instruction::SkipIfNotPressed(..) => {
+                    // XO-CHIP skips the whole of a long address load
+                    pending.push(next + disassembly.length_at(next));
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        disassembly
+    }
+
+    fn word_at(&self, address: usize) -> Option<u16> {
+        if address < PROGRAM_START || address + 2 > PROGRAM_START + self.rom.len() {
+            return None;
+        }
+
+        let offset = address - PROGRAM_START;
+        Some((self.rom[offset] as u16) << 8 | self.rom[offset + 1] as u16)
+    }
+
+    /// The instruction at `address`, provided it lies wholly inside the
+    /// program and encodes back to the same opcode, so that reassembling the
+    /// listing reproduces the program exactly.
+    fn instruction_at(&self, address: usize) -> Option<Instruction> {
+        let opcode = self.word_at(address)?;
+        if opcode == LONG_ADDRESS_OPCODE && self.word_at(address + 2).is_none() {
+            return None;
+        }
+
+        match Instruction::try_from(opcode) {
+            Ok(instruction) if instruction.to_u16() == opcode => Some(instruction),
+            _ => None,
+        }
+    }
+
+    fn length_at(&self, address: usize) -> usize {
+        if self.word_at(address) == Some(LONG_ADDRESS_OPCODE) { 4 } else { 2 }
+    }
+
+    fn add_label(&mut self, address: usize) {
+        if address >= PROGRAM_START && address < PROGRAM_START + self.rom.len() {
+            self.labels.insert(address);
+        }
+    }
+
+    /// `address` as an operand, by label when there is one.
+    fn target(&self, address: u16) -> String {
+        if self.labels.contains(&(address as usize)) {
+            label(address as usize)
+        } else {
+            format!("{:#05x}", address)
+        }
+    }
+
+    fn format_instruction(&self, address: usize, instruction: &Instruction) -> String {
+        match *instruction {
+            Instruction::JumpToAddress(target) => format!("JP {}", self.target(target)),
+            Instruction::CallSubroutine(target) => format!("CALL {}", self.target(target)),
+            Instruction::JumpToV0Address(target) => format!("JP V0, {}", self.target(target)),
+            Instruction::SetLongMemoryAddress => {
+                format!("LD I, LONG {:#06x}", self.word_at(address + 2).unwrap_or(0))
+            }
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+fn label(address: usize) -> String {
+    format!("L{:03x}", address)
+}
+
+/// One line per instruction or data word, in the syntax accepted by
+/// `assembler::assemble`, with the address and raw bytes of each line in a
+/// trailing comment.
+impl<'a> fmt::Display for Disassembly<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = PROGRAM_START + self.rom.len();
+        let mut address = PROGRAM_START;
+        while address < end {
+            if self.labels.contains(&address) {
+                writeln!(f, "{}:", label(address))?;
+            }
+
+            let offset = address - PROGRAM_START;
+            // Instructions overlapping a label are left as data, so that the
+            // label can still be placed
+            let length = self.length_at(address);
+            let overlaps_label = (1..length).any(|idx| self.labels.contains(&(address + idx)));
+            let (text, length) = match self.instruction_at(address) {
+                Some(ref instruction) if self.code.contains(&address) && !overlaps_label => {
+                    (self.format_instruction(address, instruction), length)
+                }
+                // Data runs up to the next instruction or label, so a word
+                // never swallows the first byte of either
+                _ if address + 2 <= end && !self.code.contains(&(address + 1)) &&
+                     !self.labels.contains(&(address + 1)) => {
+                    (format!("DW {:#06x}", self.word_at(address).unwrap()), 2)
+                }
+                _ => (format!("DB {:#04x}", self.rom[offset]), 1),
+            };
+
+            let bytes: Vec<String> = self.rom[offset..offset + length]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            writeln!(f, "    {:<24}; {:03x}: {}", text, address, bytes.join(""))?;
+
+            address += length;
+        }
+
+        Ok(())
+    }
+}
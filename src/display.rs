@@ -0,0 +1,80 @@
+use std::str::FromStr;
+
+use Error;
+
+/// Colours for each combination of the two XO-CHIP bitplanes, starting with
+/// the background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub colors: [(u8, u8, u8); 4],
+}
+
+impl Palette {
+    /// The colour of a grid cell.
+    pub fn color(&self, pixel: u8) -> (u8, u8, u8) {
+        self.colors[(pixel & 0x3) as usize]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette { colors: [(0, 0, 0), (255, 255, 255), (170, 170, 170), (85, 85, 85)] }
+    }
+}
+
+/// Parses between two and four comma separated hex colours, such as
+/// `000000,ffffff`. Colours that aren't given keep their defaults.
+impl FromStr for Palette {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Palette, Error> {
+        let colors: Vec<&str> = s.split(',').map(|color| color.trim()).collect();
+        if colors.len() < 2 || colors.len() > 4 {
+            return Err(format_err!("A palette needs between two and four colours"));
+        }
+
+        let mut palette = Palette::default();
+        for (idx, color) in colors.iter().enumerate() {
+            let digits = color.trim_start_matches('#');
+            let value = match u32::from_str_radix(digits, 16) {
+                Ok(value) if digits.len() == 6 => value,
+                _ => return Err(format_err!("\"{}\" is not a colour like ff8000", color)),
+            };
+            palette.colors[idx] = ((value >> 16) as u8, (value >> 8) as u8, value as u8);
+        }
+
+        Ok(palette)
+    }
+}
+
+/// Implemented by frontends that know how to present the CPU's framebuffer.
+///
+/// The grid is laid out row by row, `width * height` cells long. Each cell is
+/// a bit mask of the XO-CHIP planes lit there, bit 0 for the first plane and
+/// bit 1 for the second, which makes it an index into a `Palette`. Zero is
+/// the background.
+pub trait Renderer {
+    fn render(&mut self, grid: &[u8], width: usize, height: usize) -> Result<(), Error>;
+}
+
+/// Draws the framebuffer as text, one line per row with `#` for lit pixels
+/// and `.` for dark ones. Useful for headless runs and for comparing screens
+/// in tests.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextRenderer {
+    pub text: String,
+}
+
+impl Renderer for TextRenderer {
+    fn render(&mut self, grid: &[u8], width: usize, height: usize) -> Result<(), Error> {
+        self.text.clear();
+        for row in grid[..width * height].chunks(width) {
+            for cell in row.iter() {
+                self.text.push(if *cell != 0 { '#' } else { '.' });
+            }
+            self.text.push('\n');
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use audio::Pattern;
+use cpu::CPU;
+use platform::Platform;
+use {Error, INSTRUCTIONS_PER_FRAME};
+
+/// Runs a CPU a frame at a time.
+///
+/// Each frame executes a fixed number of instructions and then ticks the
+/// timers once, so emulated time only advances when frames are run. A
+/// frontend calling `run_frame` 60 times a second gets a stable speed
+/// however fast the host is, and headless runs behave exactly the same.
+pub struct Emulator {
+    pub cpu: CPU,
+    /// Instructions executed per frame, at most. A frame ends early when
+    /// the CPU halts or waits for the display. Can be changed at any time.
+    pub instructions_per_frame: u32,
+    /// Frames run so far.
+    pub frames: u64,
+    /// Whether the sound timer was running when the last frame ended, just
+    /// before the timers ticked.
+    pub beeping: bool,
+    /// Instructions executed so far in the current frame.
+    steps: u32,
+}
+
+impl Emulator {
+    pub fn new(cpu: CPU) -> Emulator {
+        Emulator {
+            cpu,
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frames: 0,
+            beeping: false,
+            steps: 0,
+        }
+    }
+
+    pub fn with_instructions_per_frame(mut self, instructions_per_frame: u32) -> Emulator {
+        self.instructions_per_frame = instructions_per_frame;
+        self
+    }
+
+    /// The XO-CHIP pattern the beeper plays, once the program has loaded
+    /// one. Until then it sounds like any other platform's.
+    pub fn pattern(&self) -> Option<Pattern> {
+        let cpu = &self.cpu;
+        if cpu.platform == Platform::XoChip && cpu.audio_pattern.iter().any(|bits| *bits != 0) {
+            Some(Pattern {
+                bits: cpu.audio_pattern,
+                pitch: cpu.pitch,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Runs one frame, stopping at the first error.
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        self.run_frame_with(|_, e| Err(e))
+    }
+
+    /// Runs one frame, passing errors to `on_error`. The frame carries on if
+    /// it returns `Ok`, having dealt with the error, e.g. by stepping over an
+    /// unknown opcode. Otherwise the frame stops there without ticking the
+    /// timers, and its error is returned.
+    pub fn run_frame_with<F>(&mut self, mut on_error: F) -> Result<(), Error>
+        where F: FnMut(&mut CPU, Error) -> Result<(), Error>
+    {
+        let frames = self.frames;
+        while self.frames == frames {
+            self.step_with(&mut on_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single instruction, ending the frame if it was the last
+    /// one the frame had room for.
+    pub fn step(&mut self) -> Result<(), Error> {
+        self.step_with(|_, e| Err(e))
+    }
+
+    fn step_with<F>(&mut self, mut on_error: F) -> Result<(), Error>
+        where F: FnMut(&mut CPU, Error) -> Result<(), Error>
+    {
+        if self.steps < self.instructions_per_frame && !self.cpu.vblank_wait &&
+           !self.cpu.halted {
+            if let Err(e) = self.cpu.step() {
+                on_error(&mut self.cpu, e)?;
+            }
+            self.steps += 1;
+        }
+
+        // A frame ends early when the CPU halts or waits for the display
+        if self.steps >= self.instructions_per_frame || self.cpu.vblank_wait ||
+           self.cpu.halted {
+            self.beeping = self.cpu.sound_timer > 0;
+            self.cpu.tick_timers();
+            self.cpu.vblank();
+            self.frames += 1;
+            self.steps = 0;
+        }
+
+        Ok(())
+    }
+}
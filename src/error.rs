@@ -0,0 +1,53 @@
+use std::fmt;
+
+use Fail;
+
+/// Faults raised by the CPU while running a program.
+#[derive(Debug, PartialEq)]
+pub enum CpuError {
+    UnknownOpcode { opcode: u16, pc: usize },
+    /// A call went deeper than the stack allows. `trace` holds the return
+    /// addresses on the stack, outermost first.
+    StackOverflow { pc: usize, trace: Vec<usize> },
+    /// A return was made with nothing on the stack.
+    StackUnderflow { pc: usize },
+    /// Memory was accessed past its end while addresses are set to fault.
+    AddressOutOfRange { address: usize },
+    /// The interpreter area was written to while it is write-protected.
+    WriteProtected { address: usize },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpuError::UnknownOpcode { opcode, pc } => {
+                write!(f, "Unknown opcode {:#06x} at {:#05x}", opcode, pc)
+            }
+            CpuError::StackOverflow { pc, ref trace } => {
+                let trace: Vec<String> = trace.iter().map(|ret| format!("{:#05x}", ret)).collect();
+                write!(f, "Stack overflow at {:#05x} (call trace: {})", pc, trace.join(", "))
+            }
+            CpuError::StackUnderflow { pc } => write!(f, "Stack underflow at {:#05x}", pc),
+            CpuError::AddressOutOfRange { address } => {
+                write!(f, "Address {:#06x} is out of range", address)
+            }
+            CpuError::WriteProtected { address } => {
+                write!(f, "Write to protected address {:#05x}", address)
+            }
+        }
+    }
+}
+
+impl Fail for CpuError {}
+
+/// A word that doesn't encode any instruction.
+#[derive(Debug, PartialEq)]
+pub struct InvalidOpcode(pub u16);
+
+impl fmt::Display for InvalidOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid opcode {:#06x}", self.0)
+    }
+}
+
+impl Fail for InvalidOpcode {}
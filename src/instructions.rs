@@ -1,4 +1,8 @@
+use std::convert::TryFrom;
+use std::fmt;
+
 use bitrange::BitRange;
+use error::InvalidOpcode;
 
 type GPR = u8;
 type Address = u16;
@@ -8,11 +12,20 @@ type HalfWord = u8;
 pub enum Instruction {
     ClearDisplay,
     Return,
+    ScrollDown(HalfWord),
+    ScrollUp(HalfWord),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
     JumpToAddress(Address),
     CallSubroutine(Address),
     SkipIfEqual(GPR, HalfWord),
     SkipIfNotEqual(GPR, HalfWord),
     SkipIfEqualRegister(GPR, HalfWord),
+    SaveRange(GPR, GPR),
+    LoadRange(GPR, GPR),
     LoadConst(GPR, HalfWord),
     AddConst(GPR, HalfWord),
     AssignValue(GPR, GPR),
@@ -26,6 +39,7 @@ pub enum Instruction {
     ShiftLeft(GPR, GPR),
     SkipIfNotEqualRegister(GPR, GPR),
     SetMemoryAddress(Address),
+    SetLongMemoryAddress,
     JumpToV0Address(Address),
     BitwiseRandom(GPR, HalfWord),
     DrawSprite(GPR, GPR, HalfWord),
@@ -37,9 +51,15 @@ pub enum Instruction {
     SetSound(GPR),
     AddOffset(GPR),
     SetMemoryForFont(GPR),
+    SetMemoryForBigFont(GPR),
     SetBCD(GPR),
     DumpReg(GPR),
     LoadReg(GPR),
+    StoreFlags(GPR),
+    LoadFlags(GPR),
+    SelectPlane(HalfWord),
+    LoadAudioPattern,
+    SetPitch(GPR),
 }
 
 fn first(value: &u16) -> u8 {
@@ -67,64 +87,230 @@ fn last_three(value: &u16) -> u16 {
 }
 
 impl Instruction {
+    /// Decodes an opcode. Like the original interpreters, bits an instruction
+    /// doesn't use are ignored, so `to_u16` only gives back the same opcode
+    /// for its canonical encoding.
     pub fn from_u16(value: &u16) -> Option<Instruction> {
-        match first(&value) {
+        match first(&value) {
             0x0 => {
-                match last_two(&value) {
-                    0xE0 => Some(Instruction::ClearDisplay),
-                    0xEE => Some(Instruction::Return),
-                    _ => None,
+                match last_two(&value) {
+                    0xE0 => Some(Instruction::ClearDisplay),
+                    0xEE => Some(Instruction::Return),
+                    0xFB => Some(Instruction::ScrollRight),
+                    0xFC => Some(Instruction::ScrollLeft),
+                    0xFD => Some(Instruction::Exit),
+                    0xFE => Some(Instruction::LowRes),
+                    0xFF => Some(Instruction::HighRes),
+                    _ if third(value) == 0xC => Some(Instruction::ScrollDown(last(value))),
+                    _ if third(value) == 0xD => Some(Instruction::ScrollUp(last(value))),
+                    _ => None,
+                }
+            }
+            0x1 => Some(Instruction::JumpToAddress(last_three(&value))),
+            0x2 => Some(Instruction::CallSubroutine(last_three(&value))),
+            0x3 => Some(Instruction::SkipIfEqual(second(&value), last_two(&value))),
+            0x4 => Some(Instruction::SkipIfNotEqual(second(&value), last_two(&value))),
+            0x5 => {
+                match last(value) {
+                    0x0 => Some(Instruction::SkipIfEqualRegister(second(value), third(value))),
+                    0x2 => Some(Instruction::SaveRange(second(value), third(value))),
+                    0x3 => Some(Instruction::LoadRange(second(value), third(value))),
+                    _ => None,
                 }
-            }
-            0x1 => Some(Instruction::JumpToAddress(last_three(&value))),
-            0x2 => Some(Instruction::CallSubroutine(last_three(&value))),
-            0x3 => Some(Instruction::SkipIfEqual(second(&value), last_two(&value))),
-            0x4 => Some(Instruction::SkipIfNotEqual(second(&value), last_two(&value))),
-            0x5 => Some(Instruction::SkipIfEqualRegister(second(&value), third(&value))),
-            0x6 => Some(Instruction::LoadConst(second(&value), last_two(&value))),
-            0x7 => Some(Instruction::AddConst(second(&value), last_two(&value))),
+            }
+            0x6 => Some(Instruction::LoadConst(second(&value), last_two(&value))),
+            0x7 => Some(Instruction::AddConst(second(&value), last_two(&value))),
             0x8 => {
-                match last(&value) {
-                    0x0 => Some(Instruction::AssignValue(second(&value), third(&value))),
-                    0x1 => Some(Instruction::SetOr(second(&value), third(&value))),
-                    0x2 => Some(Instruction::SetAnd(second(&value), third(&value))),
-                    0x3 => Some(Instruction::SetXor(second(&value), third(&value))),
-                    0x4 => Some(Instruction::Add(second(&value), third(&value))),
-                    0x5 => Some(Instruction::Subtract(second(&value), third(&value))),
-                    0x6 => Some(Instruction::ShiftRight(second(&value), third(&value))),
-                    0x7 => Some(Instruction::Reduce(second(&value), third(&value))),
-                    0xE => Some(Instruction::ShiftLeft(second(&value), third(&value))),
-                    _ => None,
+                match last(&value) {
+                    0x0 => Some(Instruction::AssignValue(second(&value), third(&value))),
+                    0x1 => Some(Instruction::SetOr(second(&value), third(&value))),
+                    0x2 => Some(Instruction::SetAnd(second(&value), third(&value))),
+                    0x3 => Some(Instruction::SetXor(second(&value), third(&value))),
+                    0x4 => Some(Instruction::Add(second(&value), third(&value))),
+                    0x5 => Some(Instruction::Subtract(second(&value), third(&value))),
+                    0x6 => Some(Instruction::ShiftRight(second(&value), third(&value))),
+                    0x7 => Some(Instruction::Reduce(second(&value), third(&value))),
+                    0xE => Some(Instruction::ShiftLeft(second(&value), third(&value))),
+                    _ => None,
                 }
-            }
-            0x9 => Some(Instruction::SkipIfNotEqualRegister(second(&value), third(&value))),
-            0xA => Some(Instruction::SetMemoryAddress(last_three(&value))),
-            0xB => Some(Instruction::JumpToV0Address(last_three(&value))),
-            0xC => Some(Instruction::BitwiseRandom(second(&value), last_two(&value))),
-            0xD => Some(Instruction::DrawSprite(second(&value), third(&value), last(&value))),
+            }
+            0x9 => Some(Instruction::SkipIfNotEqualRegister(second(&value), third(&value))),
+            0xA => Some(Instruction::SetMemoryAddress(last_three(&value))),
+            0xB => Some(Instruction::JumpToV0Address(last_three(&value))),
+            0xC => Some(Instruction::BitwiseRandom(second(&value), last_two(&value))),
+            0xD => Some(Instruction::DrawSprite(second(&value), third(&value), last(&value))),
             0xE => {
-                match last_two(&value) {
-                    0x9E => Some(Instruction::SkipIfPressed(second(&value))),
-                    0xA1 => Some(Instruction::SkipIfNotPressed(second(&value))),
-                    _ => None,
+                match last_two(&value) {
+                    0x9E => Some(Instruction::SkipIfPressed(second(&value))),
+                    0xA1 => Some(Instruction::SkipIfNotPressed(second(&value))),
+                    _ => None,
                 }
-            }
+            }
             0xF => {
-                match last_two(&value) {
-                    0x07 => Some(Instruction::LoadDelay(second(&value))),
-                    0x0A => Some(Instruction::WaitForPress(second(&value))),
-                    0x15 => Some(Instruction::SetDelay(second(&value))),
-                    0x18 => Some(Instruction::SetSound(second(&value))),
-                    0x1E => Some(Instruction::AddOffset(second(&value))),
-                    0x29 => Some(Instruction::SetMemoryForFont(second(&value))),
-                    0x33 => Some(Instruction::SetBCD(second(&value))),
-                    0x55 => Some(Instruction::DumpReg(second(&value))),
-                    0x65 => Some(Instruction::LoadReg(second(&value))),
-                    _ => None,
+                match last_two(&value) {
+                    0x00 if second(value) == 0x0 => Some(Instruction::SetLongMemoryAddress),
+                    0x01 => Some(Instruction::SelectPlane(second(value))),
+                    0x02 if second(value) == 0x0 => Some(Instruction::LoadAudioPattern),
+                    0x07 => Some(Instruction::LoadDelay(second(&value))),
+                    0x0A => Some(Instruction::WaitForPress(second(&value))),
+                    0x15 => Some(Instruction::SetDelay(second(&value))),
+                    0x18 => Some(Instruction::SetSound(second(&value))),
+                    0x1E => Some(Instruction::AddOffset(second(&value))),
+                    0x29 => Some(Instruction::SetMemoryForFont(second(&value))),
+                    0x30 => Some(Instruction::SetMemoryForBigFont(second(value))),
+                    0x33 => Some(Instruction::SetBCD(second(&value))),
+                    0x3A => Some(Instruction::SetPitch(second(value))),
+                    0x55 => Some(Instruction::DumpReg(second(&value))),
+                    0x65 => Some(Instruction::LoadReg(second(&value))),
+                    0x75 => Some(Instruction::StoreFlags(second(value))),
+                    0x85 => Some(Instruction::LoadFlags(second(value))),
+                    _ => None,
                 }
-            }
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction back into its opcode. For
+    /// `SetLongMemoryAddress` this is only the first word; the address
+    /// follows it.
+    pub fn to_u16(&self) -> u16 {
+        fn xy(base: u16, x: GPR, y: GPR) -> u16 {
+            base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4
+        }
+
+        fn xkk(base: u16, x: GPR, byte: HalfWord) -> u16 {
+            base | (x as u16 & 0xF) << 8 | byte as u16
+        }
+
+        fn x(base: u16, x: GPR) -> u16 {
+            base | (x as u16 & 0xF) << 8
+        }
+
+        fn nnn(base: u16, address: Address) -> u16 {
+            base | (address & 0xFFF)
+        }
+
+        match *self {
+            Instruction::ClearDisplay => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::JumpToAddress(address) => nnn(0x1000, address),
+            Instruction::CallSubroutine(address) => nnn(0x2000, address),
+            Instruction::SkipIfEqual(vx, byte) => xkk(0x3000, vx, byte),
+            Instruction::SkipIfNotEqual(vx, byte) => xkk(0x4000, vx, byte),
+            Instruction::SkipIfEqualRegister(vx, vy) => xy(0x5000, vx, vy),
+            Instruction::SaveRange(vx, vy) => xy(0x5002, vx, vy),
+            Instruction::LoadRange(vx, vy) => xy(0x5003, vx, vy),
+            Instruction::LoadConst(vx, byte) => xkk(0x6000, vx, byte),
+            Instruction::AddConst(vx, byte) => xkk(0x7000, vx, byte),
+            Instruction::AssignValue(vx, vy) => xy(0x8000, vx, vy),
+            Instruction::SetOr(vx, vy) => xy(0x8001, vx, vy),
+            Instruction::SetAnd(vx, vy) => xy(0x8002, vx, vy),
+            Instruction::SetXor(vx, vy) => xy(0x8003, vx, vy),
+            Instruction::Add(vx, vy) => xy(0x8004, vx, vy),
+            Instruction::Subtract(vx, vy) => xy(0x8005, vx, vy),
+            Instruction::ShiftRight(vx, vy) => xy(0x8006, vx, vy),
+            Instruction::Reduce(vx, vy) => xy(0x8007, vx, vy),
+            Instruction::ShiftLeft(vx, vy) => xy(0x800E, vx, vy),
+            Instruction::SkipIfNotEqualRegister(vx, vy) => xy(0x9000, vx, vy),
+            Instruction::SetMemoryAddress(address) => nnn(0xA000, address),
+            Instruction::SetLongMemoryAddress => 0xF000,
+            Instruction::JumpToV0Address(address) => nnn(0xB000, address),
+            Instruction::BitwiseRandom(vx, byte) => xkk(0xC000, vx, byte),
+            Instruction::DrawSprite(vx, vy, n) => xy(0xD000, vx, vy) | (n as u16 & 0xF),
+            Instruction::SkipIfPressed(vx) => x(0xE09E, vx),
+            Instruction::SkipIfNotPressed(vx) => x(0xE0A1, vx),
+            Instruction::LoadDelay(vx) => x(0xF007, vx),
+            Instruction::WaitForPress(vx) => x(0xF00A, vx),
+            Instruction::SetDelay(vx) => x(0xF015, vx),
+            Instruction::SetSound(vx) => x(0xF018, vx),
+            Instruction::AddOffset(vx) => x(0xF01E, vx),
+            Instruction::SetMemoryForFont(vx) => x(0xF029, vx),
+            Instruction::SetMemoryForBigFont(vx) => x(0xF030, vx),
+            Instruction::SetBCD(vx) => x(0xF033, vx),
+            Instruction::DumpReg(vx) => x(0xF055, vx),
+            Instruction::LoadReg(vx) => x(0xF065, vx),
+            Instruction::StoreFlags(vx) => x(0xF075, vx),
+            Instruction::LoadFlags(vx) => x(0xF085, vx),
+            Instruction::SelectPlane(n) => x(0xF001, n),
+            Instruction::LoadAudioPattern => 0xF002,
+            Instruction::SetPitch(vx) => x(0xF03A, vx),
+        }
+    }
+}
+
+impl TryFrom<u16> for Instruction {
+    type Error = InvalidOpcode;
+
+    fn try_from(value: u16) -> Result<Instruction, InvalidOpcode> {
+        Instruction::from_u16(&value).ok_or(InvalidOpcode(value))
+    }
+}
 
-            _ => None,
+/// Mnemonics in the style of Cowgod's reference, extended with the usual
+/// names for the SUPER-CHIP and XO-CHIP instructions. `SetLongMemoryAddress`
+/// is shown as `LD I, LONG`, its address being in the word that follows.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ClearDisplay => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::JumpToAddress(address) => write!(f, "JP {:#05x}", address),
+            Instruction::CallSubroutine(address) => write!(f, "CALL {:#05x}", address),
+            Instruction::SkipIfEqual(x, byte) => write!(f, "SE V{:X}, {:#04x}", x, byte),
+            Instruction::SkipIfNotEqual(x, byte) => write!(f, "SNE V{:X}, {:#04x}", x, byte),
+            Instruction::SkipIfEqualRegister(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadConst(x, byte) => write!(f, "LD V{:X}, {:#04x}", x, byte),
+            Instruction::AddConst(x, byte) => write!(f, "ADD V{:X}, {:#04x}", x, byte),
+            Instruction::AssignValue(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::SetOr(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::SetAnd(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::SetXor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Subtract(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Reduce(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfNotEqualRegister(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::SetMemoryAddress(address) => write!(f, "LD I, {:#05x}", address),
+            Instruction::SetLongMemoryAddress => write!(f, "LD I, LONG"),
+            Instruction::JumpToV0Address(address) => write!(f, "JP V0, {:#05x}", address),
+            Instruction::BitwiseRandom(x, byte) => write!(f, "RND V{:X}, {:#04x}", x, byte),
+            Instruction::DrawSprite(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfPressed(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfNotPressed(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitForPress(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddOffset(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::SetMemoryForFont(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::SetMemoryForBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::SetBCD(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::DumpReg(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadReg(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Instruction::SelectPlane(n) => write!(f, "PLANE {}", n),
+            Instruction::LoadAudioPattern => write!(f, "AUDIO"),
+            Instruction::SetPitch(x) => write!(f, "PITCH V{:X}", x),
         }
     }
 }
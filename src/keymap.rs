@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use Error;
+
+/// Host key bound to each keypad key, by name, indexed by keypad key.
+///
+/// Names are left for the frontend to interpret, e.g. SDL key names such
+/// as `Q`, `1` or `Left`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    pub keys: [String; 16],
+}
+
+impl Default for KeyMap {
+    /// The usual layout, mapping the COSMAC VIP keypad onto the left hand
+    /// side of a QWERTY keyboard:
+    ///
+    /// ```text
+    /// 1 2 3 C      1 2 3 4
+    /// 4 5 6 D  ->  Q W E R
+    /// 7 8 9 E      A S D F
+    /// A 0 B F      Z X C V
+    /// ```
+    fn default() -> KeyMap {
+        let names = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F",
+                     "V"];
+        let mut keys: [String; 16] = Default::default();
+        for (key, name) in keys.iter_mut().zip(names.iter()) {
+            *key = name.to_string();
+        }
+
+        KeyMap { keys }
+    }
+}
+
+impl KeyMap {
+    /// Reads a mapping made of `<keypad key> = <host key>` lines, with the
+    /// keypad key in hex. Blank lines and lines starting with `#` are ignored
+    /// and any key not mentioned keeps its default binding.
+    pub fn parse(text: &str) -> Result<KeyMap, Error> {
+        let mut map = KeyMap::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let name = parts.next().unwrap_or("").trim();
+            if name.is_empty() {
+                return Err(format_err!("Line {}: expected `<key> = <host key>`", number + 1));
+            }
+
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(key) if key < 16 => key,
+                _ => {
+                    return Err(format_err!("Line {}: \"{}\" is not a keypad key", number + 1, key))
+                }
+            };
+
+            map.keys[key as usize] = name.to_string();
+        }
+
+        Ok(map)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<KeyMap, Error> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        KeyMap::parse(&text)
+    }
+
+    /// Where the mapping for a given program is kept: next to the program,
+    /// with `.keys` added to its name.
+    pub fn path_for_rom<P: AsRef<Path>>(rom: P) -> PathBuf {
+        let mut path = rom.as_ref().as_os_str().to_owned();
+        path.push(".keys");
+        PathBuf::from(path)
+    }
+
+    /// The mapping kept alongside a program, or the default if there isn't one.
+    pub fn for_rom<P: AsRef<Path>>(rom: P) -> Result<KeyMap, Error> {
+        let path = KeyMap::path_for_rom(rom);
+        if path.exists() {
+            KeyMap::load(path)
+        } else {
+            Ok(KeyMap::default())
+        }
+    }
+}
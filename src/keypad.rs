@@ -0,0 +1,93 @@
+/// State of the 16-key hex keypad.
+///
+/// Besides which keys are currently held, the keypad remembers which keys
+/// went down or came back up since `clear_edges` was last called, so that
+/// instructions waiting for input don't miss a quick tap between checks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keypad {
+    held: u16,
+    pressed: u16,
+    released: u16,
+}
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad::default()
+    }
+
+    pub fn press(&mut self, key: u8) {
+        let bit = 1 << (key & 0xF);
+        if self.held & bit == 0 {
+            self.pressed |= bit;
+        }
+        self.held |= bit;
+    }
+
+    pub fn release(&mut self, key: u8) {
+        let bit = 1 << (key & 0xF);
+        // Only count releases of keys that went down after the edges were
+        // cleared, so a key held beforehand can't complete a wait
+        if self.held & bit != 0 && self.pressed & bit != 0 {
+            self.released |= bit;
+        }
+        self.held &= !bit;
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.held & (1 << (key & 0xF)) != 0
+    }
+
+    /// Bit mask of the keys currently held, bit n being key n.
+    pub fn held(&self) -> u16 {
+        self.held
+    }
+
+    /// Presses and releases keys as needed so that exactly those in `mask`
+    /// are held.
+    pub fn set_held(&mut self, mask: u16) {
+        for key in 0..16 {
+            if mask & (1 << key) != 0 {
+                self.press(key);
+            } else {
+                self.release(key);
+            }
+        }
+    }
+
+    /// Lowest key pressed since the edges were last cleared.
+    pub fn pressed_key(&self) -> Option<u8> {
+        lowest_key(self.pressed)
+    }
+
+    /// Lowest key both pressed and released since the edges were last cleared.
+    pub fn released_key(&self) -> Option<u8> {
+        lowest_key(self.released)
+    }
+
+    /// The held, pressed and released keys as bit masks, which is all of
+    /// the keypad's state.
+    pub fn to_masks(&self) -> [u16; 3] {
+        [self.held, self.pressed, self.released]
+    }
+
+    pub fn from_masks(masks: [u16; 3]) -> Keypad {
+        Keypad {
+            held: masks[0],
+            pressed: masks[1],
+            released: masks[2],
+        }
+    }
+
+    pub fn clear_edges(&mut self) {
+        self.pressed = 0;
+        self.released = 0;
+    }
+}
+
+fn lowest_key(keys: u16) -> Option<u8> {
+    if keys == 0 {
+        None
+    } else {
+        Some(keys.trailing_zeros() as u8)
+    }
+}
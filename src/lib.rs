@@ -1,25 +1,48 @@
 extern crate byteorder;
 extern crate rand;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
+#[macro_use]
 extern crate failure;
 
 mod bitrange;
+pub mod assembler;
+pub mod audio;
 pub mod instructions;
+pub mod keymap;
+pub mod keypad;
 pub mod cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod display;
+pub mod emulator;
+pub mod error;
+pub mod memory;
+pub mod movie;
+pub mod platform;
+pub mod quirks;
+pub mod rng;
+pub mod screenshot;
+pub mod state;
+pub mod trace;
+pub mod video;
+#[cfg(feature = "sdl")]
+pub mod sdl;
 
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 pub use failure::{Error, Fail};
 
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use sdl2::EventPump;
-use sdl2::Sdl;
+#[cfg(feature = "sdl")]
+pub use sdl::Context;
 
-pub const FRAME_TICK: Duration = Duration::from_millis(16);
-pub const CPU_TICK: Duration = Duration::from_millis(2);
+pub const TIMER_FREQUENCY: u32 = 60;
+pub const FRAME_TICK: Duration = Duration::from_nanos(1_000_000_000 / TIMER_FREQUENCY as u64);
+pub const INSTRUCTIONS_PER_SECOND: u32 = 500;
+pub const INSTRUCTIONS_PER_FRAME: u32 = INSTRUCTIONS_PER_SECOND / TIMER_FREQUENCY;
 
 pub const FONT4X5: [u8; 80] = [
     0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
@@ -40,14 +63,24 @@ pub const FONT4X5: [u8; 80] = [
     0xf0, 0x80, 0xf0, 0x80, 0x80, // F
 ];
 
-pub struct Context {
-    pub sdl_context: Option<Sdl>,
-    pub canvas: Option<Canvas<Window>>,
-    pub events: Option<EventPump>,
-    pub grid: Vec<u8>,
-    pub key_map: [u8; 16],
-}
-
+pub const FONT8X10: [u8; 160] = [
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xff, 0xff, // 1
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // 2
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 3
+    0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0x03, 0x03, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 5
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 6
+    0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18, // 7
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 8
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 9
+    0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, // A
+    0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, // B
+    0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0xff, 0x3c, // C
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // D
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // E
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // F
+];
 
 pub fn read_binary(filename: &String) -> Result<Vec<u8>, Error> {
     let mut file = File::open(filename)?;
@@ -55,3 +88,18 @@ pub fn read_binary(filename: &String) -> Result<Vec<u8>, Error> {
     file.read_to_end(&mut buf)?;
     Ok(buf)
 }
+
+/// The first `<rom>.<kind><n>.<extension>` next to the program that doesn't
+/// exist yet, counting up from 1.
+fn numbered_path_for_rom<P: AsRef<Path>>(rom: P, kind: &str, extension: &str) -> PathBuf {
+    let mut number = 1;
+    loop {
+        let mut path = rom.as_ref().as_os_str().to_owned();
+        path.push(format!(".{}{}.{}", kind, number, extension));
+        let path = PathBuf::from(path);
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
@@ -4,16 +4,69 @@ extern crate sdl2;
 extern crate failure;
 
 use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
 use std::time::Instant;
 use std::process::exit;
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, LSHIFTMOD, RSHIFTMOD};
 
 use failure::{Error, err_msg};
-use chip8::{read_binary, Context, FRAME_TICK, CPU_TICK};
+use chip8::{read_binary, Context, FRAME_TICK, INSTRUCTIONS_PER_FRAME, TIMER_FREQUENCY};
+use chip8::audio::{Audio, Pattern, Tone, SAMPLE_RATE};
 use chip8::cpu::CPU;
-use chip8::instructions::Instruction;
+use chip8::display::Palette;
+use chip8::emulator::Emulator;
+use chip8::error::CpuError;
+use chip8::keymap::KeyMap;
+use chip8::memory::AddressMode;
+use chip8::movie::Movie;
+use chip8::platform::Platform;
+use chip8::screenshot::{self, CaptureOptions, Screenshot};
+use chip8::sdl::{SdlAudio, WindowRenderer};
+use chip8::state;
+use chip8::trace::{TraceFormat, Tracer};
+use chip8::video::{self, VideoRecorder};
+
+/// How many times larger than the grid screenshots and videos are by default.
+const SCREENSHOT_SCALE: usize = 8;
+
+/// Fastest speed the speed hotkeys go up to.
+const MAX_IPF: u32 = 10_000;
+
+/// Highest beeper pitch the audio device can play, half its sample rate.
+const MAX_BEEP_FREQUENCY: f32 = SAMPLE_RATE as f32 / 2.0;
+
+/// Most frames the loop runs back to back to catch up after the host stalls.
+const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// What to do when the program reaches an opcode the CPU doesn't know.
+enum OpcodePolicy {
+    /// Stop running the program.
+    Halt,
+    /// Step over the opcode silently.
+    Skip,
+    /// Report the opcode and then step over it.
+    Log,
+}
+
+impl FromStr for OpcodePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OpcodePolicy, Error> {
+        match s {
+            "halt" => Ok(OpcodePolicy::Halt),
+            "skip" => Ok(OpcodePolicy::Skip),
+            "log" => Ok(OpcodePolicy::Log),
+            _ => Err(format_err!("Unknown opcode policy \"{}\"", s)),
+        }
+    }
+}
 
 fn init_canvas(context: &mut Context) -> Result<&Context, Error> {
     let sdl_context = match sdl2::init() {
@@ -50,11 +103,77 @@ fn init_event_subsystem(context: &mut Context) -> Result<&Context, Error> {
     }
 }
 
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        _ => None,
+    }
+}
+
+fn save_state(cpu: &CPU, path: &Path) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    cpu.save_state(&mut writer)
+}
+
+fn load_state(cpu: &mut CPU, path: &Path) -> Result<(), Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    cpu.load_state(&mut reader)
+}
+
+/// Parses an inclusive range of hex addresses such as `200-2ff`.
+fn parse_range(text: &str) -> Option<Range<usize>> {
+    let mut bounds = text.splitn(2, '-');
+    let start = usize::from_str_radix(bounds.next()?, 16).ok()?;
+    let end = usize::from_str_radix(bounds.next()?, 16).ok()?;
+    Some(start..end + 1)
+}
+
+/// Instructions per frame after a speed hotkey, or `None` for keys that
+/// aren't one. `=` doubles the speed and `-` halves it.
+fn adjust_speed(keycode: Keycode, instructions_per_frame: u32) -> Option<u32> {
+    match keycode {
+        Keycode::Equals | Keycode::KpPlus => Some((instructions_per_frame * 2).min(MAX_IPF)),
+        Keycode::Minus | Keycode::KpMinus => Some((instructions_per_frame / 2).max(1)),
+        _ => None,
+    }
+}
+
+fn start_video<P: AsRef<Path>>(path: P, scale: usize, palette: Palette) -> Option<VideoRecorder> {
+    match VideoRecorder::create(&path, scale, palette) {
+        Ok(recorder) => {
+            println!("Recording video to \"{}\"", path.as_ref().display());
+            Some(recorder)
+        }
+        Err(e) => {
+            println!("Failed to start recording video: {}", e);
+            None
+        }
+    }
+}
+
+fn stop_video(recorder: &mut VideoRecorder) {
+    match recorder.finish() {
+        Err(e) => println!("Failed to save video: {}", e),
+        Ok(_) => println!("Saved {} frames of video", recorder.frames),
+    }
+}
+
+fn update_audio(audio: &mut Option<SdlAudio>, playing: bool, pattern: Option<Pattern>) {
+    if let Some(ref mut audio) = *audio {
+        if let Err(e) = audio.update(playing, pattern) {
+            println!("Failed to update audio: {}", e);
+        }
+    }
+}
+
 fn main() {
     let mut context = Context {
         canvas: None,
         grid: vec![0; 2046],
-        key_map: [0; 16],
+        key_map: [None; 16],
         events: None,
         sdl_context: None,
     };
@@ -67,7 +186,211 @@ fn main() {
         Ok(_) => (),
     }
 
-    let filename = env::args().nth(1).expect("filename?");
+    let mut filename = None;
+    let mut platform = Platform::Chip8;
+    let mut quirks = None;
+    let mut seed = None;
+    let mut ipf = INSTRUCTIONS_PER_FRAME;
+    let mut tone = Tone::default();
+    let mut key_map_path = None;
+    let mut opcode_policy = OpcodePolicy::Halt;
+    let mut address_mode = AddressMode::Wrap;
+    let mut protect_interpreter = false;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_range = None;
+    let mut trace_mnemonics = vec![];
+    let mut record_path = None;
+    let mut replay_path = None;
+    let mut capture = CaptureOptions::new(SCREENSHOT_SCALE);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                platform = match args.next().map(|name| name.parse()) {
+                    Some(Ok(platform)) => platform,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--platform expects one of chip8, schip or xochip");
+                        exit(1);
+                    }
+                }
+            }
+            "--quirks" => {
+                quirks = match args.next().map(|name| name.parse()) {
+                    Some(Ok(quirks)) => Some(quirks),
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--quirks expects one of vip, chip48, schip or xochip");
+                        exit(1);
+                    }
+                }
+            }
+            "--ipf" => {
+                ipf = match args.next().map(|value| value.parse()) {
+                    Some(Ok(ipf)) if ipf > 0 => ipf,
+                    _ => {
+                        println!("--ipf expects the number of instructions to run per frame");
+                        exit(1);
+                    }
+                }
+            }
+            "--ips" => {
+                ipf = match args.next().map(|value| value.parse::<u32>()) {
+                    Some(Ok(ips)) if ips > 0 => (ips / TIMER_FREQUENCY).max(1),
+                    _ => {
+                        println!("--ips expects the number of instructions to run per second");
+                        exit(1);
+                    }
+                }
+            }
+            "--beep-frequency" => {
+                tone.frequency = match args.next().map(|value| value.parse()) {
+                    Some(Ok(frequency)) if frequency > 0.0 && frequency < MAX_BEEP_FREQUENCY => {
+                        frequency
+                    }
+                    _ => {
+                        println!("--beep-frequency expects a pitch between 0 and {} Hz",
+                                 MAX_BEEP_FREQUENCY);
+                        exit(1);
+                    }
+                }
+            }
+            "--beep-volume" => {
+                tone.volume = match args.next().map(|value| value.parse()) {
+                    Some(Ok(volume)) if (0.0..=1.0).contains(&volume) => volume,
+                    _ => {
+                        println!("--beep-volume expects a volume between 0.0 and 1.0");
+                        exit(1);
+                    }
+                }
+            }
+            "--beep-waveform" => {
+                tone.waveform = match args.next().map(|name| name.parse()) {
+                    Some(Ok(waveform)) => waveform,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--beep-waveform expects square, triangle, sawtooth or sine");
+                        exit(1);
+                    }
+                }
+            }
+            "--keys" => {
+                key_map_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        println!("--keys expects the path of a key mapping file");
+                        exit(1);
+                    }
+                }
+            }
+            "--on-unknown-opcode" => {
+                opcode_policy = match args.next().map(|name| name.parse()) {
+                    Some(Ok(policy)) => policy,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--on-unknown-opcode expects one of halt, skip or log");
+                        exit(1);
+                    }
+                }
+            }
+            "--address-faults" => address_mode = AddressMode::Fault,
+            "--protect-interpreter" => protect_interpreter = true,
+            "--trace" => {
+                trace_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        println!("--trace expects the path of the file to write the trace to");
+                        exit(1);
+                    }
+                }
+            }
+            "--trace-format" => {
+                trace_format = match args.next().map(|name| name.parse()) {
+                    Some(Ok(format)) => format,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                    None => {
+                        println!("--trace-format expects text or binary");
+                        exit(1);
+                    }
+                }
+            }
+            "--trace-range" => {
+                trace_range = match args.next().as_ref().and_then(|range| parse_range(range)) {
+                    Some(range) => Some(range),
+                    None => {
+                        println!("--trace-range expects a range of addresses in hex, e.g. 200-2ff");
+                        exit(1);
+                    }
+                }
+            }
+            "--trace-only" => {
+                trace_mnemonics = match args.next() {
+                    Some(mnemonics) => {
+                        mnemonics.split(',').map(|mnemonic| mnemonic.trim().to_string()).collect()
+                    }
+                    None => {
+                        println!("--trace-only expects a list of mnemonics, e.g. DRW,CALL");
+                        exit(1);
+                    }
+                }
+            }
+            "--seed" => {
+                seed = match args.next().map(|value| value.parse()) {
+                    Some(Ok(seed)) => Some(seed),
+                    _ => {
+                        println!("--seed expects a number to seed random numbers with");
+                        exit(1);
+                    }
+                }
+            }
+            "--record" => {
+                record_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        println!("--record expects the path of the movie file to write");
+                        exit(1);
+                    }
+                }
+            }
+            "--replay" => {
+                replay_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        println!("--replay expects the path of a movie file");
+                        exit(1);
+                    }
+                }
+            }
+            _ => {
+                match capture.parse_arg(&arg, &mut args) {
+                    Ok(true) => {}
+                    Ok(false) => filename = Some(arg),
+                    Err(e) => {
+                        println!("{}", e);
+                        exit(1);
+                    }
+                }
+            }
+        }
+    }
+    let filename = filename.expect("filename?");
+    let CaptureOptions { scale, palette, video_path } = capture;
 
     let data = match read_binary(&filename) {
         Ok(data) => data,
@@ -77,13 +400,99 @@ fn main() {
         }
     };
 
-    let mut cpu = CPU::new(&data, context.canvas);
+    let replay = match replay_path {
+        Some(ref path) => {
+            match Movie::load_file(path) {
+                Ok(movie) => Some(movie),
+                Err(e) => {
+                    println!("Failed to load movie \"{}\": {}", path, e);
+                    exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // A replayed movie brings its own configuration, seed and speed
+    let mut emulator = match replay {
+        Some(ref movie) => {
+            match movie.start(&data) {
+                Ok(emulator) => emulator,
+                Err(e) => {
+                    println!("Failed to replay movie: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        None => {
+            let quirks = quirks.unwrap_or(platform.quirks());
+            let mut cpu = match CPU::new(&data, platform, quirks) {
+                Ok(cpu) => cpu,
+                Err(e) => {
+                    println!("Failed to load \"{}\": {}", filename, e);
+                    exit(1);
+                }
+            };
+            if let Some(seed) = seed {
+                cpu = cpu.with_seed(seed);
+            }
+            Emulator::new(cpu).with_instructions_per_frame(ipf)
+        }
+    };
+    emulator.cpu.memory.address_mode = address_mode;
+    emulator.cpu.memory.protect_interpreter = protect_interpreter;
+    if let Some(ref path) = trace_path {
+        match Tracer::create(path, trace_format) {
+            Ok(mut tracer) => {
+                tracer.pc_range = trace_range;
+                tracer.mnemonics = trace_mnemonics;
+                emulator.cpu.tracer = Some(tracer);
+            }
+            Err(e) => {
+                println!("Failed to create trace \"{}\": {}", path, e);
+                exit(1);
+            }
+        }
+    }
+    let mut recording = record_path.as_ref().map(|_| Movie::new(&data, &emulator));
+    let mut replay_frames = replay.map(|movie| movie.frames.into_iter());
+    let mut video = video_path.and_then(|path| start_video(path, scale, palette));
+    let mut window = context.canvas.take().map(|canvas| {
+        WindowRenderer {
+            canvas,
+            palette,
+        }
+    });
+
+    let key_map = match key_map_path {
+        Some(ref path) => KeyMap::load(path),
+        None => KeyMap::for_rom(&filename),
+    };
+    if let Err(e) = key_map.and_then(|key_map| context.set_key_map(&key_map)) {
+        println!("Failed to load key mapping: {}", e);
+        exit(1);
+    }
+
+    let mut audio = match context.sdl_context {
+        Some(ref sdl_context) => {
+            match SdlAudio::new(sdl_context, tone) {
+                Ok(audio) => Some(audio),
+                Err(e) => {
+                    println!("Failed to initialise audio: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
 
     let mut event_pump = context
         .events
+        .take()
         .expect("Event subsystem should have been available, but it wasn't!");
 
-    let mut cpu_last = Instant::now();
+    let mut paused = false;
+    let mut stopped = false;
     let mut frame_last = Instant::now();
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -91,45 +500,156 @@ fn main() {
                 Event::Quit { .. } |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
 
-                Event::KeyDown { keycode: Some(Keycode::Space), .. } => cpu.keys[15] ^= 1,
-
-                Event::KeyDown { keycode: Some(Keycode::Left), .. } => cpu.keys[4] = 1,
-                Event::KeyDown { keycode: Some(Keycode::Right), .. } => cpu.keys[6] = 1,
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => cpu.keys[8] = 1,
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => cpu.keys[2] = 1,
-                Event::KeyDown { keycode: Some(Keycode::Return), .. } => cpu.keys[5] = 1,
+                Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. } => {
+                    if let Some(key) = context.keypad_key(keycode) {
+                        if replay_frames.is_none() {
+                            emulator.cpu.keys.press(key);
+                        }
+                    } else if let Some(slot) = state_slot(keycode) {
+                        let path = state::path_for_rom(&filename, slot);
+                        if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                            match save_state(&emulator.cpu, &path) {
+                                Err(e) => println!("Failed to save state {}: {}", slot, e),
+                                Ok(_) => println!("Saved state {}", slot),
+                            }
+                        } else if recording.is_some() || replay_frames.is_some() {
+                            // Restoring a state would break the movie's input
+                            println!("States can't be loaded while recording or replaying");
+                        } else {
+                            match load_state(&mut emulator.cpu, &path) {
+                                Err(e) => println!("Failed to load state {}: {}", slot, e),
+                                Ok(_) => println!("Loaded state {}", slot),
+                            }
+                        }
+                    } else if keycode == Keycode::F12 {
+                        let path = screenshot::path_for_rom(&filename);
+                        match Screenshot::of(&emulator.cpu, scale, palette).save(&path) {
+                            Err(e) => println!("Failed to save screenshot: {}", e),
+                            Ok(_) => println!("Saved screenshot to \"{}\"", path.display()),
+                        }
+                    } else if keycode == Keycode::F11 {
+                        match video.take() {
+                            Some(mut recorder) => stop_video(&mut recorder),
+                            None => {
+                                video = start_video(video::path_for_rom(&filename), scale, palette)
+                            }
+                        }
+                    } else if keycode == Keycode::Space {
+                        paused = !paused;
+                    } else if let Some(ipf) = adjust_speed(keycode,
+                                                           emulator.instructions_per_frame) {
+                        if recording.is_some() || replay_frames.is_some() {
+                            // Movies are replayed at the speed they start with
+                            println!("The speed can't change while recording or replaying");
+                        } else {
+                            emulator.instructions_per_frame = ipf;
+                            println!("{} instructions per frame", ipf);
+                        }
+                    }
+                }
 
-                Event::KeyUp { keycode: Some(Keycode::Left), .. } => cpu.keys[4] = 0,
-                Event::KeyUp { keycode: Some(Keycode::Right), .. } => cpu.keys[6] = 0,
-                Event::KeyUp { keycode: Some(Keycode::Up), .. } => cpu.keys[8] = 0,
-                Event::KeyUp { keycode: Some(Keycode::Down), .. } => cpu.keys[2] = 0,
-                Event::KeyUp { keycode: Some(Keycode::Return), .. } => cpu.keys[5] = 0,
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key) = context.keypad_key(keycode) {
+                        if replay_frames.is_none() {
+                            emulator.cpu.keys.release(key);
+                        }
+                    }
+                }
 
                 _ => {}
             }
         }
 
-        if cpu.keys[15] != 1 {
-            if cpu_last.elapsed() >= CPU_TICK {
-                match cpu.fetch_opcode() {
-                    Ok(raw_opcode) => {
-                        if let Some(instruction) = Instruction::from_u16(&raw_opcode) {
-                            match cpu.do_instruction(&instruction) {
-                                Err(e) => println!("Instruction execution failed: {}", e),
-                                Ok(_) => (),
-                            }
+        if emulator.cpu.halted {
+            break 'running;
+        }
+
+        let elapsed = frame_last.elapsed();
+        if elapsed >= FRAME_TICK {
+            if let Some(ref mut frames) = replay_frames {
+                if !paused && !stopped {
+                    match frames.next() {
+                        Some(keys) => emulator.cpu.keys = keys,
+                        None => {
+                            println!("Replay finished");
+                            stopped = true;
                         }
-                    },
-                    Err(e) => println!("Could not fetch opcode: {}", e)
+                    }
                 }
+            }
+            if let Some(ref mut movie) = recording {
+                if !paused && !stopped {
+                    movie.record_frame(&emulator.cpu.keys);
+                }
+            }
+
+            if !paused && !stopped {
+                let result = emulator.run_frame_with(|cpu, e| {
+                    let unknown_opcode = matches!(e.downcast_ref::<CpuError>(),
+                                                  Some(&CpuError::UnknownOpcode { .. }));
 
-                cpu_last = Instant::now();
+                    match opcode_policy {
+                        OpcodePolicy::Skip if unknown_opcode => cpu.pc += 2,
+                        OpcodePolicy::Log if unknown_opcode => {
+                            println!("{}, skipping", e);
+                            cpu.pc += 2;
+                        }
+                        _ => return Err(e),
+                    }
+                    Ok(())
+                });
+
+                // Videos only advance with emulated frames, so pauses and
+                // slow hosts don't show up in them
+                if let Some(ref mut recorder) = video {
+                    if let Err(e) = recorder.capture(&emulator.cpu) {
+                        println!("Failed to record video frame: {}", e);
+                    }
+                }
+
+                match result {
+                    Ok(_) => update_audio(&mut audio, emulator.beeping, emulator.pattern()),
+                    Err(e) => {
+                        println!("Halting: {}", e);
+                        update_audio(&mut audio, false, None);
+                        stopped = true;
+                    }
+                }
+            } else {
+                update_audio(&mut audio, false, None);
+            }
+
+            if let Some(ref mut window) = window {
+                if let Err(e) = emulator.cpu.show(window) {
+                    println!("Failed to draw frame: {}", e);
+                }
             }
+            // Past a few frames behind, drop the rest rather than racing
+            // through them
+            if elapsed > FRAME_TICK * MAX_CATCH_UP_FRAMES {
+                frame_last = Instant::now();
+            } else {
+                frame_last += FRAME_TICK;
+            }
+        } else {
+            thread::sleep(FRAME_TICK - elapsed);
+        }
+    }
+
+    if let Some(mut recorder) = video {
+        stop_video(&mut recorder);
+    }
+
+    if let Some(ref mut tracer) = emulator.cpu.tracer {
+        if let Err(e) = tracer.flush() {
+            println!("Failed to write trace: {}", e);
         }
+    }
 
-        if frame_last.elapsed() >= FRAME_TICK {
-            cpu.show();
-            frame_last = Instant::now();
+    if let (Some(movie), Some(path)) = (recording, record_path) {
+        match movie.save_file(&path) {
+            Err(e) => println!("Failed to save movie \"{}\": {}", path, e),
+            Ok(_) => println!("Saved {} frames to \"{}\"", movie.frames.len(), path),
         }
     }
-}
+}
\ No newline at end of file
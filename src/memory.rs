@@ -0,0 +1,115 @@
+use error::CpuError;
+use instructions::Instruction;
+use Error;
+
+/// Start of program space; everything below belongs to the interpreter and
+/// its fonts.
+pub const PROGRAM_START: usize = 0x200;
+
+/// How accesses beyond the end of memory are handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressMode {
+    /// Addresses wrap around to the start of memory, as they did on
+    /// interpreters that only decoded the low address bits.
+    Wrap,
+    /// Addresses past the end of memory raise `CpuError::AddressOutOfRange`.
+    Fault,
+}
+
+/// The CPU's address space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    data: Vec<u8>,
+    pub address_mode: AddressMode,
+    /// Refuse writes below `PROGRAM_START`, raising `CpuError::WriteProtected`.
+    pub protect_interpreter: bool,
+}
+
+impl Memory {
+    pub fn new(size: usize) -> Memory {
+        Memory::from_bytes(vec![0; size])
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Memory {
+        Memory {
+            data,
+            address_mode: AddressMode::Wrap,
+            protect_interpreter: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// `address` brought back inside memory when addresses wrap. Otherwise
+    /// it's left alone, to fault when it's used.
+    pub fn wrap(&self, address: usize) -> usize {
+        match self.address_mode {
+            AddressMode::Wrap => address % self.data.len(),
+            AddressMode::Fault => address,
+        }
+    }
+
+    fn resolve(&self, address: usize) -> Result<usize, Error> {
+        if address < self.data.len() {
+            return Ok(address);
+        }
+
+        match self.address_mode {
+            AddressMode::Wrap => Ok(address % self.data.len()),
+            AddressMode::Fault => Err(CpuError::AddressOutOfRange { address }.into()),
+        }
+    }
+
+    pub fn read(&self, address: usize) -> Result<u8, Error> {
+        let address = self.resolve(address)?;
+        Ok(self.data[address])
+    }
+
+    pub fn read_u16(&self, address: usize) -> Result<u16, Error> {
+        Ok((self.read(address)? as u16) << 8 | self.read(address + 1)? as u16)
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) -> Result<(), Error> {
+        let address = self.resolve(address)?;
+        if self.protect_interpreter && address < PROGRAM_START {
+            return Err(CpuError::WriteProtected { address }.into());
+        }
+
+        self.data[address] = value;
+        Ok(())
+    }
+
+    /// Copies `data` in starting at `address`, ignoring write protection.
+    /// Used to set memory up before a program runs.
+    pub fn load(&mut self, address: usize, data: &[u8]) -> Result<(), Error> {
+        let end = address + data.len();
+        if end > self.data.len() {
+            return Err(CpuError::AddressOutOfRange { address: end - 1 }.into());
+        }
+
+        self.data[address..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Overwrites the opcode at `address` with `instruction`, ignoring write
+    /// protection. `SetLongMemoryAddress` is refused, as its address isn't
+    /// part of the instruction.
+    pub fn patch(&mut self, address: usize, instruction: &Instruction) -> Result<(), Error> {
+        if *instruction == Instruction::SetLongMemoryAddress {
+            return Err(format_err!("LD I, LONG takes four bytes and can't be patched"));
+        }
+
+        let opcode = instruction.to_u16();
+        self.load(address, &[(opcode >> 8) as u8, opcode as u8])
+    }
+}
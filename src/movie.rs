@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::CPU;
+use emulator::Emulator;
+use keypad::Keypad;
+use platform::Platform;
+use quirks::Quirks;
+use Error;
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u8 = 1;
+
+/// FNV-1a hash of a program, used to check a movie is replayed against the
+/// program it was recorded with.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The keypad input of a run, one snapshot per frame, along with everything
+/// else needed to play the run back exactly: the random seed, the machine's
+/// configuration and the number of instructions per frame.
+///
+/// Each frame holds the keypad as it was just before that frame's
+/// instructions ran, including the keys pressed and released since the
+/// edges were last cleared, so a replay sees the same input at the same
+/// instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    pub frames: Vec<Keypad>,
+}
+
+impl Movie {
+    /// Starts a movie of `emulator` running `rom`. The CPU must not have run
+    /// yet, as its random number generator's current state becomes the seed.
+    pub fn new(rom: &[u8], emulator: &Emulator) -> Movie {
+        let cpu = &emulator.cpu;
+        Movie {
+            rom_hash: rom_hash(rom),
+            seed: cpu.rng.state,
+            platform: cpu.platform,
+            quirks: cpu.quirks,
+            instructions_per_frame: emulator.instructions_per_frame,
+            frames: vec![],
+        }
+    }
+
+    /// Adds the keypad state the next frame starts with.
+    pub fn record_frame(&mut self, keys: &Keypad) {
+        self.frames.push(keys.clone());
+    }
+
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+
+        writer.write_u64::<BigEndian>(self.rom_hash)?;
+        writer.write_u64::<BigEndian>(self.seed)?;
+        writer.write_u8(self.platform.to_u8())?;
+        writer.write_u8(self.quirks.to_bits())?;
+        writer.write_u32::<BigEndian>(self.instructions_per_frame)?;
+
+        writer.write_u32::<BigEndian>(self.frames.len() as u32)?;
+        for frame in self.frames.iter() {
+            for mask in frame.to_masks().iter() {
+                writer.write_u16::<BigEndian>(*mask)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Movie, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(format_err!("Not a movie"));
+        }
+
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(format_err!("Unsupported movie version {}", version));
+        }
+
+        let rom_hash = reader.read_u64::<BigEndian>()?;
+        let seed = reader.read_u64::<BigEndian>()?;
+        let platform = Platform::from_u8(reader.read_u8()?)?;
+        let quirks = Quirks::from_bits(reader.read_u8()?);
+        let instructions_per_frame = reader.read_u32::<BigEndian>()?;
+
+        // The count isn't trusted with an allocation up front, frames are only
+        // added as they're read
+        let count = reader.read_u32::<BigEndian>()?;
+        let mut frames = vec![];
+        for _ in 0..count {
+            let mut masks = [0; 3];
+            for mask in masks.iter_mut() {
+                *mask = reader.read_u16::<BigEndian>()?;
+            }
+            frames.push(Keypad::from_masks(masks));
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            platform,
+            quirks,
+            instructions_per_frame,
+            frames,
+        })
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Movie, Error> {
+        Movie::load(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// An emulator set up to run `rom` the way the movie was recorded.
+    pub fn start(&self, rom: &Vec<u8>) -> Result<Emulator, Error> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(format_err!("The movie was recorded with a different program"));
+        }
+
+        let cpu = CPU::new(rom, self.platform, self.quirks)?.with_seed(self.seed);
+        Ok(Emulator::new(cpu).with_instructions_per_frame(self.instructions_per_frame))
+    }
+
+    /// Plays the whole movie back without a display and returns the emulator
+    /// as it is at the end.
+    pub fn replay(&self, rom: &Vec<u8>) -> Result<Emulator, Error> {
+        let mut emulator = self.start(rom)?;
+        for frame in self.frames.iter() {
+            emulator.cpu.keys = frame.clone();
+            emulator.run_frame()?;
+        }
+
+        Ok(emulator)
+    }
+}
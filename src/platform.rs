@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use quirks::Quirks;
+use Error;
+
+/// The interpreter family a program was written for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn memory_size(&self) -> usize {
+        match *self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
+
+    /// How many nested subroutine calls the interpreter has room for.
+    pub fn stack_depth(&self) -> usize {
+        match *self {
+            Platform::Chip8 => 12,
+            Platform::SuperChip | Platform::XoChip => 16,
+        }
+    }
+
+    /// The quirks programs written for this platform usually expect.
+    pub fn quirks(&self) -> Quirks {
+        match *self {
+            Platform::Chip8 => Quirks::cosmac_vip(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    /// The platform's number in save states and movies.
+    pub fn to_u8(&self) -> u8 {
+        match *self {
+            Platform::Chip8 => 0,
+            Platform::SuperChip => 1,
+            Platform::XoChip => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Result<Platform, Error> {
+        match value {
+            0 => Ok(Platform::Chip8),
+            1 => Ok(Platform::SuperChip),
+            2 => Ok(Platform::XoChip),
+            _ => Err(format_err!("Unknown platform {}", value)),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Platform, Error> {
+        match s.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(format_err!("Unknown platform \"{}\"", s)),
+        }
+    }
+}
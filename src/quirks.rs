@@ -0,0 +1,121 @@
+use std::str::FromStr;
+
+use Error;
+
+/// Behaviour of the opcodes that differ between interpreters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift Vy into Vx rather than shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// Fx55/Fx65 leave I pointing just past the last register accessed.
+    pub load_store_increments_i: bool,
+    /// Bnnn jumps to nnn + Vx, where x is the top nibble of nnn, instead of V0.
+    pub jump_uses_vx: bool,
+    /// Sprites wrap around the edges of the screen instead of being clipped.
+    pub sprite_wrap: bool,
+    /// 8xy1/8xy2/8xy3 reset VF to zero.
+    pub vf_reset: bool,
+    /// Drawing a sprite waits for the next vertical blank.
+    pub display_wait: bool,
+    /// Fx0A completes when the key is released rather than when it is pressed.
+    pub wait_for_release: bool,
+    /// In high resolution, Dxyn sets VF to the number of sprite rows that
+    /// collided or were clipped by the bottom of the screen, rather than 1.
+    pub collision_count: bool,
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            sprite_wrap: false,
+            vf_reset: true,
+            display_wait: true,
+            wait_for_release: true,
+            collision_count: false,
+        }
+    }
+
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            sprite_wrap: false,
+            vf_reset: false,
+            display_wait: false,
+            wait_for_release: false,
+            collision_count: false,
+        }
+    }
+
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            sprite_wrap: false,
+            vf_reset: false,
+            display_wait: false,
+            wait_for_release: false,
+            collision_count: true,
+        }
+    }
+
+    pub fn xo_chip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            sprite_wrap: true,
+            vf_reset: false,
+            display_wait: false,
+            wait_for_release: true,
+            collision_count: false,
+        }
+    }
+
+    /// The quirks packed into a byte, one bit each, for save states and
+    /// movies.
+    pub fn to_bits(&self) -> u8 {
+        (self.shift_uses_vy as u8) | (self.load_store_increments_i as u8) << 1 |
+        (self.jump_uses_vx as u8) << 2 | (self.sprite_wrap as u8) << 3 |
+        (self.vf_reset as u8) << 4 | (self.display_wait as u8) << 5 |
+        (self.wait_for_release as u8) << 6 | (self.collision_count as u8) << 7
+    }
+
+    pub fn from_bits(value: u8) -> Quirks {
+        Quirks {
+            shift_uses_vy: value & 1 != 0,
+            load_store_increments_i: value & (1 << 1) != 0,
+            jump_uses_vx: value & (1 << 2) != 0,
+            sprite_wrap: value & (1 << 3) != 0,
+            vf_reset: value & (1 << 4) != 0,
+            display_wait: value & (1 << 5) != 0,
+            wait_for_release: value & (1 << 6) != 0,
+            collision_count: value & (1 << 7) != 0,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::cosmac_vip()
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Quirks, Error> {
+        match s.to_lowercase().as_str() {
+            "vip" | "cosmac-vip" => Ok(Quirks::cosmac_vip()),
+            "chip48" | "chip-48" => Ok(Quirks::chip48()),
+            "schip" | "superchip" | "super-chip" => Ok(Quirks::schip()),
+            "xochip" | "xo-chip" => Ok(Quirks::xo_chip()),
+            _ => Err(format_err!("Unknown quirks preset \"{}\"", s)),
+        }
+    }
+}
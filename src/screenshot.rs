@@ -0,0 +1,288 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+use cpu::CPU;
+use display::Palette;
+use {numbered_path_for_rom, Error};
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Largest amount of data a stored deflate block can hold.
+const STORED_BLOCK_SIZE: usize = 0xffff;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// Black and white netpbm bitmap.
+    Pbm,
+    /// Greyscale netpbm graymap.
+    Pgm,
+}
+
+impl ImageFormat {
+    /// The format a file name's extension asks for.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ImageFormat, Error> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => extension.parse(),
+            None => Err(format_err!("\"{}\" has no image extension", path.display())),
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ImageFormat, Error> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "pbm" => Ok(ImageFormat::Pbm),
+            "pgm" => Ok(ImageFormat::Pgm),
+            _ => Err(format_err!("Unknown image format \"{}\"", s)),
+        }
+    }
+}
+
+/// The first `<rom>.screenshot<n>.png` next to the program that doesn't exist
+/// yet.
+pub fn path_for_rom<P: AsRef<Path>>(rom: P) -> PathBuf {
+    numbered_path_for_rom(rom, "screenshot", "png")
+}
+
+/// How screenshots and videos are drawn, and where a video is recorded to,
+/// from the `--scale`, `--palette` and `--video` options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureOptions {
+    pub scale: usize,
+    pub palette: Palette,
+    pub video_path: Option<String>,
+}
+
+impl CaptureOptions {
+    pub fn new(scale: usize) -> CaptureOptions {
+        CaptureOptions {
+            scale,
+            palette: Palette::default(),
+            video_path: None,
+        }
+    }
+
+    /// Takes `arg`'s value from `args` when it's one of the capture options.
+    /// Returns false for any other argument, leaving `args` alone.
+    pub fn parse_arg<I>(&mut self, arg: &str, args: &mut I) -> Result<bool, Error>
+        where I: Iterator<Item = String>
+    {
+        match arg {
+            "--scale" => {
+                self.scale = match args.next().map(|value| value.parse()) {
+                    Some(Ok(scale)) if scale > 0 => scale,
+                    _ => {
+                        return Err(format_err!("--scale expects how many times to enlarge \
+                                                screenshots and videos"))
+                    }
+                }
+            }
+            "--palette" => {
+                self.palette = match args.next().map(|colors| colors.parse()) {
+                    Some(Ok(palette)) => palette,
+                    Some(Err(e)) => return Err(e),
+                    None => {
+                        return Err(format_err!("--palette expects two to four hex colours, \
+                                                e.g. 000000,ffffff"))
+                    }
+                }
+            }
+            "--video" => {
+                self.video_path = match args.next() {
+                    Some(path) => Some(path),
+                    None => {
+                        return Err(format_err!("--video expects the path of a .gif, .rgb or \
+                                                .raw file"))
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// A copy of the framebuffer, each pixel scaled up to a `scale` by `scale`
+/// square, that can be written out as an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    /// Grid cells, row by row, as indices into `palette`.
+    pub pixels: Vec<u8>,
+    pub palette: Palette,
+}
+
+impl Screenshot {
+    pub fn new(grid: &[u8],
+               width: usize,
+               height: usize,
+               scale: usize,
+               palette: Palette)
+               -> Screenshot {
+        let scale = scale.max(1);
+        let mut pixels = Vec::with_capacity(width * height * scale * scale);
+        for row in grid[..width * height].chunks(width) {
+            for _ in 0..scale {
+                for cell in row.iter() {
+                    for _ in 0..scale {
+                        pixels.push(*cell & 0x3);
+                    }
+                }
+            }
+        }
+
+        Screenshot {
+            width: width * scale,
+            height: height * scale,
+            pixels,
+            palette,
+        }
+    }
+
+    /// The CPU's screen as it is now.
+    pub fn of(cpu: &CPU, scale: usize, palette: Palette) -> Screenshot {
+        Screenshot::new(&cpu.grid, cpu.width(), cpu.height(), scale, palette)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, format: ImageFormat) -> Result<(), Error> {
+        match format {
+            ImageFormat::Png => self.write_png(writer),
+            ImageFormat::Pbm => self.write_pbm(writer),
+            ImageFormat::Pgm => self.write_pgm(writer),
+        }
+    }
+
+    /// Writes the image in the format its extension names.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let format = ImageFormat::from_path(&path)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Pixels whose colour is dark come out black, the rest white.
+    pub fn write_pbm<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write!(writer, "P4\n{} {}\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width) {
+            for byte in row.chunks(8) {
+                let bits = byte.iter().enumerate().fold(0u8, |bits, (idx, pixel)| {
+                    let black = self.grey(*pixel) < 128;
+                    bits | (black as u8) << (7 - idx)
+                });
+                writer.write_u8(bits)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_pgm<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write!(writer, "P5\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.pixels.iter() {
+            writer.write_u8(self.grey(*pixel))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes an indexed colour PNG. The image data is stored without
+    /// compression, which keeps the encoder small and screenshots are tiny
+    /// anyway.
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(PNG_SIGNATURE)?;
+
+        let mut header = vec![];
+        header.write_u32::<BigEndian>(self.width as u32)?;
+        header.write_u32::<BigEndian>(self.height as u32)?;
+        // 8 bits per pixel, indexed colour, default compression, filtering
+        // and no interlacing
+        header.extend_from_slice(&[8, 3, 0, 0, 0]);
+        write_chunk(writer, b"IHDR", &header)?;
+
+        let mut palette = vec![];
+        for &(r, g, b) in self.palette.colors.iter() {
+            palette.extend_from_slice(&[r, g, b]);
+        }
+        write_chunk(writer, b"PLTE", &palette)?;
+
+        // Each row starts with its filter type, which is always none
+        let mut scanlines = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_chunk(writer, b"IDAT", &zlib_stored(&scanlines))?;
+
+        write_chunk(writer, b"IEND", &[])
+    }
+
+    fn grey(&self, pixel: u8) -> u8 {
+        let (r, g, b) = self.palette.color(pixel);
+        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+    }
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), Error> {
+    writer.write_u32::<BigEndian>(data.len() as u32)?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let mut checked = kind.to_vec();
+    checked.extend_from_slice(data);
+    writer.write_u32::<BigEndian>(crc32(&checked))?;
+    Ok(())
+}
+
+/// A zlib stream holding `data` in uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // 32K window, no preset dictionary, check bits making the header a
+    // multiple of 31
+    let mut stream = vec![0x78, 0x01];
+
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(STORED_BLOCK_SIZE).collect()
+    };
+    for (idx, block) in blocks.iter().enumerate() {
+        let last = idx == blocks.len() - 1;
+        stream.push(last as u8);
+        stream.write_u16::<LittleEndian>(block.len() as u16).unwrap();
+        stream.write_u16::<LittleEndian>(!(block.len() as u16)).unwrap();
+        stream.extend_from_slice(block);
+    }
+
+    stream.write_u32::<BigEndian>(adler32(data)).unwrap();
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,132 @@
+use std::cmp;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+use sdl2::Sdl;
+
+use failure::err_msg;
+
+use audio::{Audio, Pattern, Tone, ToneGenerator, SAMPLE_RATE};
+use display::{Palette, Renderer};
+use keymap::KeyMap;
+use Error;
+
+pub struct Context {
+    pub sdl_context: Option<Sdl>,
+    pub canvas: Option<Canvas<Window>>,
+    pub events: Option<EventPump>,
+    pub grid: Vec<u8>,
+    pub key_map: [Option<Keycode>; 16],
+}
+
+impl Context {
+    /// Binds the host keys named in `map` to the keypad.
+    pub fn set_key_map(&mut self, map: &KeyMap) -> Result<(), Error> {
+        for (binding, name) in self.key_map.iter_mut().zip(map.keys.iter()) {
+            match Keycode::from_name(name) {
+                Some(keycode) => *binding = Some(keycode),
+                None => return Err(format_err!("Unknown key \"{}\"", name)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keypad key bound to a host key, if any.
+    pub fn keypad_key(&self, keycode: Keycode) -> Option<u8> {
+        self.key_map
+            .iter()
+            .position(|binding| *binding == Some(keycode))
+            .map(|key| key as u8)
+    }
+}
+
+/// Draws the screen into the window in the given palette's colours.
+pub struct WindowRenderer {
+    pub canvas: Canvas<Window>,
+    pub palette: Palette,
+}
+
+impl Renderer for WindowRenderer {
+    fn render(&mut self, grid: &[u8], width: usize, height: usize) -> Result<(), Error> {
+        // Scale to whatever fits the window so both resolutions fill it
+        let (output_width, output_height) = self.canvas.output_size().map_err(err_msg)?;
+        let pixel_size = cmp::max(1, cmp::min(output_width / width as u32,
+                                              output_height / height as u32));
+
+        let (r, g, b) = self.palette.color(0);
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = grid[(y * width) + x] & 0x3;
+                if pixel != 0 {
+                    let (r, g, b) = self.palette.color(pixel);
+                    self.canvas.set_draw_color(Color::RGB(r, g, b));
+                    self.canvas
+                        .fill_rect(Rect::new(x as i32 * pixel_size as i32,
+                                             y as i32 * pixel_size as i32,
+                                             pixel_size,
+                                             pixel_size))
+                        .map_err(err_msg)?;
+                }
+            }
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+}
+
+struct ToneCallback(ToneGenerator);
+
+impl AudioCallback for ToneCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.fill(out);
+    }
+}
+
+/// Plays the beeper through the default SDL audio device.
+pub struct SdlAudio {
+    device: AudioDevice<ToneCallback>,
+}
+
+impl SdlAudio {
+    pub fn new(sdl_context: &Sdl, tone: Tone) -> Result<SdlAudio, Error> {
+        let audio_subsystem = sdl_context.audio().map_err(err_msg)?;
+        let spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem
+            .open_playback(None, &spec, |spec| {
+                ToneCallback(ToneGenerator::new(tone, spec.freq as u32))
+            })
+            .map_err(err_msg)?;
+
+        Ok(SdlAudio { device })
+    }
+}
+
+impl Audio for SdlAudio {
+    fn update(&mut self, playing: bool, pattern: Option<Pattern>) -> Result<(), Error> {
+        self.device.lock().0.pattern = pattern;
+        if playing {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::{CPU, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH};
+use keypad::Keypad;
+use memory::Memory;
+use platform::Platform;
+use quirks::Quirks;
+use rng::Rng;
+use Error;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 1;
+
+/// Where a program's save state for the given slot is kept: next to the
+/// program, with `.state<slot>` added to its name.
+pub fn path_for_rom<P: AsRef<Path>>(rom: P, slot: u8) -> PathBuf {
+    let mut path = rom.as_ref().as_os_str().to_owned();
+    path.push(format!(".state{}", slot));
+    PathBuf::from(path)
+}
+
+impl CPU {
+    /// Writes a snapshot of the whole machine, including its memory, display
+    /// and quirks, so that it can be resumed later with `load_state`.
+    pub fn save_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+
+        writer.write_u8(self.platform.to_u8())?;
+        writer.write_u8(self.quirks.to_bits())?;
+
+        writer.write_all(&self.regs)?;
+        writer.write_u16::<BigEndian>(self.address)?;
+        writer.write_u32::<BigEndian>(self.pc as u32)?;
+        writer.write_u8(self.stack.len() as u8)?;
+        for ret in self.stack.iter() {
+            writer.write_u32::<BigEndian>(*ret as u32)?;
+        }
+
+        writer.write_u8(self.delay_timer)?;
+        writer.write_u8(self.sound_timer)?;
+        writer.write_u16::<BigEndian>(self.keys.held())?;
+
+        writer.write_u8(self.waiting_for_key as u8)?;
+        writer.write_u8(self.halted as u8)?;
+        writer.write_u8(self.vblank_wait as u8)?;
+        writer.write_u8(self.hires as u8)?;
+        writer.write_u8(self.planes)?;
+        writer.write_all(&self.flags)?;
+        writer.write_all(&self.audio_pattern)?;
+        writer.write_u8(self.pitch)?;
+        writer.write_u64::<BigEndian>(self.rng.state)?;
+
+        writer.write_u32::<BigEndian>(self.grid.len() as u32)?;
+        writer.write_all(&self.grid)?;
+
+        let memory = self.memory.as_slice();
+        writer.write_u32::<BigEndian>(memory.len() as u32)?;
+        writer.write_all(memory)?;
+
+        Ok(())
+    }
+
+    /// Replaces the whole machine with a snapshot written by `save_state`.
+    /// Nothing is changed if the snapshot can't be read.
+    pub fn load_state<R: Read>(&mut self, reader: &mut R) -> Result<(), Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(format_err!("Not a save state"));
+        }
+
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(format_err!("Unsupported save state version {}", version));
+        }
+
+        let platform = Platform::from_u8(reader.read_u8()?)?;
+        let quirks = Quirks::from_bits(reader.read_u8()?);
+
+        let mut regs = [0; 16];
+        reader.read_exact(&mut regs)?;
+        let address = reader.read_u16::<BigEndian>()?;
+        let pc = reader.read_u32::<BigEndian>()? as usize;
+        let stack_len = reader.read_u8()? as usize;
+        if stack_len > platform.stack_depth() {
+            return Err(format_err!("Save state stack holds {} calls, more than the {} allowed",
+                                   stack_len,
+                                   platform.stack_depth()));
+        }
+        let mut stack = vec![];
+        for _ in 0..stack_len {
+            stack.push(reader.read_u32::<BigEndian>()? as usize);
+        }
+
+        let delay_timer = reader.read_u8()?;
+        let sound_timer = reader.read_u8()?;
+        let mut keys = Keypad::new();
+        keys.set_held(reader.read_u16::<BigEndian>()?);
+        keys.clear_edges();
+
+        let waiting_for_key = reader.read_u8()? != 0;
+        let halted = reader.read_u8()? != 0;
+        let vblank_wait = reader.read_u8()? != 0;
+        let hires = reader.read_u8()? != 0;
+        let planes = reader.read_u8()?;
+        let mut flags = [0; 16];
+        reader.read_exact(&mut flags)?;
+        let mut audio_pattern = [0; 16];
+        reader.read_exact(&mut audio_pattern)?;
+        let pitch = reader.read_u8()?;
+        let rng = Rng::new(reader.read_u64::<BigEndian>()?);
+
+        let grid_len = reader.read_u32::<BigEndian>()? as usize;
+        let expected = if hires {
+            HIRES_WIDTH * HIRES_HEIGHT
+        } else {
+            LORES_WIDTH * LORES_HEIGHT
+        };
+        if grid_len != expected {
+            return Err(format_err!("Save state screen is {} cells, expected {}",
+                                   grid_len,
+                                   expected));
+        }
+        let mut grid = vec![0; grid_len];
+        reader.read_exact(&mut grid)?;
+
+        let memory_len = reader.read_u32::<BigEndian>()? as usize;
+        if memory_len != platform.memory_size() {
+            return Err(format_err!("Save state memory is {} bytes, expected {}",
+                                   memory_len,
+                                   platform.memory_size()));
+        }
+        let mut memory = vec![0; memory_len];
+        reader.read_exact(&mut memory)?;
+
+        self.platform = platform;
+        self.quirks = quirks;
+        self.regs = regs;
+        self.address = address;
+        self.pc = pc;
+        self.stack = stack;
+        self.stack_depth = platform.stack_depth();
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.keys = keys;
+        self.waiting_for_key = waiting_for_key;
+        self.halted = halted;
+        self.vblank_wait = vblank_wait;
+        self.hires = hires;
+        self.planes = planes;
+        self.flags = flags;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.rng = rng;
+        self.grid = grid;
+        let mut memory = Memory::from_bytes(memory);
+        memory.address_mode = self.memory.address_mode;
+        memory.protect_interpreter = self.memory.protect_interpreter;
+        self.memory = memory;
+
+        Ok(())
+    }
+}
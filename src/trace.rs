@@ -0,0 +1,247 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::CPU;
+use instructions::Instruction;
+use Error;
+
+const MAGIC: &[u8; 4] = b"C8TR";
+const VERSION: u8 = 1;
+
+/// How a trace is written out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// One line per instruction, e.g.
+    /// `200: 6345  LD V3, 0x45  V3 00->45  DT=00 ST=00`.
+    Text,
+    /// A `C8TR` header followed by one variable length record per
+    /// instruction, as read back by `read_binary_trace`.
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TraceFormat, Error> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format_err!("Unknown trace format \"{}\"", s)),
+        }
+    }
+}
+
+/// What a single executed instruction did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub pc: usize,
+    pub opcode: u16,
+    /// Registers the instruction changed, as `(register, old, new)`.
+    pub registers: Vec<(u8, u8, u8)>,
+    /// The index register as `(old, new)`, if the instruction changed it.
+    pub address: Option<(u16, u16)>,
+    /// Timer values once the instruction has run.
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceEntry {
+    /// Compares the CPU, after running the instruction at `pc`, with its
+    /// registers and index register from before.
+    pub fn new(pc: usize,
+               opcode: u16,
+               regs_before: &[u8; 16],
+               address_before: u16,
+               cpu: &CPU)
+               -> TraceEntry {
+        let registers = (0..16)
+            .filter(|idx| regs_before[*idx] != cpu.regs[*idx])
+            .map(|idx| (idx as u8, regs_before[idx], cpu.regs[idx]))
+            .collect();
+        let address = if address_before != cpu.address {
+            Some((address_before, cpu.address))
+        } else {
+            None
+        };
+
+        TraceEntry {
+            pc,
+            opcode,
+            registers,
+            address,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+        }
+    }
+
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u16::<BigEndian>(self.pc as u16)?;
+        writer.write_u16::<BigEndian>(self.opcode)?;
+        writer.write_u8(self.delay_timer)?;
+        writer.write_u8(self.sound_timer)?;
+
+        let mask = self.registers.iter().fold(0u16, |mask, &(idx, _, _)| mask | 1 << idx);
+        writer.write_u16::<BigEndian>(mask)?;
+        for &(_, old, new) in self.registers.iter() {
+            writer.write_u8(old)?;
+            writer.write_u8(new)?;
+        }
+
+        match self.address {
+            Some((old, new)) => {
+                writer.write_u8(1)?;
+                writer.write_u16::<BigEndian>(old)?;
+                writer.write_u16::<BigEndian>(new)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+
+        Ok(())
+    }
+
+    /// Reads the next record of a binary trace, or `None` at the end of it.
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Option<TraceEntry>, Error> {
+        let pc = match reader.read_u16::<BigEndian>() {
+            Ok(pc) => pc as usize,
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let opcode = reader.read_u16::<BigEndian>()?;
+        let delay_timer = reader.read_u8()?;
+        let sound_timer = reader.read_u8()?;
+
+        let mask = reader.read_u16::<BigEndian>()?;
+        let mut registers = vec![];
+        for idx in 0..16 {
+            if mask & (1 << idx) != 0 {
+                registers.push((idx, reader.read_u8()?, reader.read_u8()?));
+            }
+        }
+
+        let address = if reader.read_u8()? != 0 {
+            Some((reader.read_u16::<BigEndian>()?, reader.read_u16::<BigEndian>()?))
+        } else {
+            None
+        };
+
+        Ok(Some(TraceEntry {
+            pc,
+            opcode,
+            registers,
+            address,
+            delay_timer,
+            sound_timer,
+        }))
+    }
+}
+
+/// The text format's line for the entry, without the line break.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let instruction = match Instruction::try_from(self.opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => "???".to_string(),
+        };
+        write!(f, "{:03x}: {:04x}  {:<20}", self.pc, self.opcode, instruction)?;
+
+        for &(idx, old, new) in self.registers.iter() {
+            write!(f, "  V{:X} {:02x}->{:02x}", idx, old, new)?;
+        }
+        if let Some((old, new)) = self.address {
+            write!(f, "  I {:04x}->{:04x}", old, new)?;
+        }
+
+        write!(f, "  DT={:02x} ST={:02x}", self.delay_timer, self.sound_timer)
+    }
+}
+
+/// Checks that `reader` holds a binary trace and reads all of its entries.
+pub fn read_binary_trace<R: Read>(reader: &mut R) -> Result<Vec<TraceEntry>, Error> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(format_err!("Not a binary trace"));
+    }
+
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(format_err!("Unsupported trace version {}", version));
+    }
+
+    let mut entries = vec![];
+    while let Some(entry) = TraceEntry::read_binary(reader)? {
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Records the instructions a CPU executes. Set it as the CPU's `tracer` to
+/// start tracing.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    /// Only trace instructions at these addresses.
+    pub pc_range: Option<Range<usize>>,
+    /// Only trace instructions with these mnemonics, e.g. `DRW` or `CALL`.
+    /// Everything is traced when empty.
+    pub mnemonics: Vec<String>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(writer: W, format: TraceFormat) -> Result<Tracer, Error> {
+        let mut writer = Box::new(writer);
+        if format == TraceFormat::Binary {
+            writer.write_all(MAGIC)?;
+            writer.write_u8(VERSION)?;
+        }
+
+        Ok(Tracer {
+            writer,
+            format,
+            pc_range: None,
+            mnemonics: vec![],
+        })
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P, format: TraceFormat) -> Result<Tracer, Error> {
+        Tracer::new(BufWriter::new(File::create(path)?), format)
+    }
+
+    /// Whether an instruction at `pc` passes the filters.
+    pub fn wants(&self, pc: usize, instruction: &Instruction) -> bool {
+        if let Some(ref range) = self.pc_range {
+            if pc < range.start || pc >= range.end {
+                return false;
+            }
+        }
+
+        if self.mnemonics.is_empty() {
+            return true;
+        }
+        let text = instruction.to_string();
+        let mnemonic = text.split_whitespace().next().unwrap_or("");
+        self.mnemonics.iter().any(|wanted| wanted.eq_ignore_ascii_case(mnemonic))
+    }
+
+    pub fn record(&mut self, entry: &TraceEntry) -> Result<(), Error> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", entry)?,
+            TraceFormat::Binary => entry.write_binary(&mut self.writer)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
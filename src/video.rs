@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use cpu::{CPU, HIRES_HEIGHT, HIRES_WIDTH};
+use display::Palette;
+use {numbered_path_for_rom, Error, TIMER_FREQUENCY};
+
+/// Shortest delay, in hundredths of a second, that viewers play GIF frames
+/// at. Anything shorter is usually slowed right down.
+const MIN_GIF_DELAY: u64 = 2;
+
+/// Largest width or height a GIF's 16 bit header fields can hold.
+const MAX_SIZE: usize = 0xffff;
+
+/// Smallest code size the palette's four colours allow.
+const LZW_MIN_CODE_SIZE: u8 = 2;
+const LZW_MAX_CODE: u16 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoFormat {
+    /// Animated GIF, looping forever.
+    Gif,
+    /// Headerless 24 bit RGB frames at 60 per second, e.g. for
+    /// `ffmpeg -f rawvideo -pix_fmt rgb24 -s WxH -r 60 -i FILE`.
+    Raw,
+}
+
+impl VideoFormat {
+    /// The format a file name's extension asks for: `.gif`, or `.rgb` or
+    /// `.raw` for raw frames.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<VideoFormat, Error> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("gif") => Ok(VideoFormat::Gif),
+            Some(extension) if extension.eq_ignore_ascii_case("rgb") ||
+                               extension.eq_ignore_ascii_case("raw") => Ok(VideoFormat::Raw),
+            _ => Err(format_err!("\"{}\" isn't a .gif, .rgb or .raw file", path.display())),
+        }
+    }
+}
+
+/// The first `<rom>.video<n>.gif` next to the program that doesn't exist yet.
+pub fn path_for_rom<P: AsRef<Path>>(rom: P) -> PathBuf {
+    numbered_path_for_rom(rom, "video", "gif")
+}
+
+/// Records the screen once per emulated frame.
+///
+/// Timing comes from the number of frames captured, at 60 a second, rather
+/// than from the host's clock, so videos play at the program's real speed
+/// however fast or slow the emulator ran. The video is the size of the high
+/// resolution screen, scaled up, so that programs switching resolution fill
+/// it either way; low resolution frames are doubled up.
+pub struct VideoRecorder {
+    writer: Box<dyn Write>,
+    format: VideoFormat,
+    palette: Palette,
+    /// Size of the video, in pixels.
+    pub width: usize,
+    pub height: usize,
+    /// Frames captured so far.
+    pub frames: u64,
+    /// GIF frame waiting to find out how long it's shown for, along with the
+    /// frame it was captured on.
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl VideoRecorder {
+    /// Fails if `scale` makes the video too large for a GIF.
+    pub fn new<W: Write + 'static>(writer: W,
+                                   format: VideoFormat,
+                                   scale: usize,
+                                   palette: Palette)
+                                   -> Result<VideoRecorder, Error> {
+        let scale = check_scale(scale)?;
+        Ok(VideoRecorder {
+            writer: Box::new(writer),
+            format,
+            palette,
+            width: HIRES_WIDTH * scale,
+            height: HIRES_HEIGHT * scale,
+            frames: 0,
+            pending: None,
+        })
+    }
+
+    /// Records into a file, in the format its extension names.
+    pub fn create<P: AsRef<Path>>(path: P,
+                                  scale: usize,
+                                  palette: Palette)
+                                  -> Result<VideoRecorder, Error> {
+        let format = VideoFormat::from_path(&path)?;
+        // Checked before the file is created, so a bad scale leaves nothing
+        // behind
+        check_scale(scale)?;
+        let writer = BufWriter::new(File::create(path)?);
+        VideoRecorder::new(writer, format, scale, palette)
+    }
+
+    /// Adds the CPU's screen as the next frame.
+    pub fn capture(&mut self, cpu: &CPU) -> Result<(), Error> {
+        self.capture_grid(&cpu.grid, cpu.width(), cpu.height())
+    }
+
+    pub fn capture_grid(&mut self,
+                        grid: &[u8],
+                        width: usize,
+                        height: usize)
+                        -> Result<(), Error> {
+        if self.frames == 0 && self.format == VideoFormat::Gif {
+            self.write_gif_header()?;
+        }
+
+        // Nearest neighbour, so each cell becomes a square block of pixels
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            let row = y * height / self.height * width;
+            for x in 0..self.width {
+                pixels.push(grid[row + x * width / self.width] & 0x3);
+            }
+        }
+
+        match self.format {
+            VideoFormat::Gif => self.add_gif_frame(pixels)?,
+            VideoFormat::Raw => {
+                for pixel in pixels.iter() {
+                    let (r, g, b) = self.palette.color(*pixel);
+                    self.writer.write_all(&[r, g, b])?;
+                }
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes out whatever is still pending and ends the video. Nothing more
+    /// should be captured afterwards.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.frames == 0 {
+            return Err(format_err!("No frames were recorded"));
+        }
+
+        if self.format == VideoFormat::Gif {
+            if let Some((pixels, start)) = self.pending.take() {
+                let delay = centiseconds(self.frames) - centiseconds(start);
+                self.write_gif_frame(&pixels, delay.max(MIN_GIF_DELAY))?;
+            }
+            self.writer.write_u8(0x3b)?;
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Holds each frame back until the next different one comes along, so
+    /// that unchanged frames only lengthen the delay of the one on screen.
+    /// A frame shown for less than `MIN_GIF_DELAY` is replaced by its
+    /// successor.
+    fn add_gif_frame(&mut self, pixels: Vec<u8>) -> Result<(), Error> {
+        let now = self.frames;
+        let start = match self.pending {
+            Some((ref pending, _)) if *pending == pixels => return Ok(()),
+            Some((_, start)) if centiseconds(now) - centiseconds(start) < MIN_GIF_DELAY => start,
+            Some((_, start)) => {
+                let (pending, _) = self.pending.take().unwrap();
+                self.write_gif_frame(&pending, centiseconds(now) - centiseconds(start))?;
+                now
+            }
+            None => now,
+        };
+
+        self.pending = Some((pixels, start));
+        Ok(())
+    }
+
+    fn write_gif_header(&mut self) -> Result<(), Error> {
+        self.writer.write_all(b"GIF89a")?;
+        self.writer.write_u16::<LittleEndian>(self.width as u16)?;
+        self.writer.write_u16::<LittleEndian>(self.height as u16)?;
+        // A global colour table of four colours, then the background colour
+        // and aspect ratio
+        self.writer.write_all(&[0x81, 0, 0])?;
+        for &(r, g, b) in self.palette.colors.iter() {
+            self.writer.write_all(&[r, g, b])?;
+        }
+
+        // Loop forever
+        self.writer.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(())
+    }
+
+    fn write_gif_frame(&mut self, pixels: &[u8], delay: u64) -> Result<(), Error> {
+        // Graphic control extension with the delay
+        self.writer.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+        self.writer.write_u16::<LittleEndian>(delay.min(0xffff) as u16)?;
+        self.writer.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole screen
+        self.writer.write_u8(0x2c)?;
+        self.writer.write_u16::<LittleEndian>(0)?;
+        self.writer.write_u16::<LittleEndian>(0)?;
+        self.writer.write_u16::<LittleEndian>(self.width as u16)?;
+        self.writer.write_u16::<LittleEndian>(self.height as u16)?;
+        self.writer.write_u8(0)?;
+
+        self.writer.write_u8(LZW_MIN_CODE_SIZE)?;
+        for block in lzw_encode(pixels).chunks(255) {
+            self.writer.write_u8(block.len() as u8)?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_u8(0)?;
+        Ok(())
+    }
+}
+
+/// The scale, of at least 1, if the video it makes fits in a GIF.
+fn check_scale(scale: usize) -> Result<usize, Error> {
+    let scale = scale.max(1);
+    if scale > MAX_SIZE / HIRES_WIDTH {
+        return Err(format_err!("A scale of {} makes the video wider than {} pixels",
+                               scale,
+                               MAX_SIZE));
+    }
+
+    Ok(scale)
+}
+
+/// When a frame starts, in the hundredths of a second GIF delays are
+/// measured in.
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + TIMER_FREQUENCY as u64 / 2) / TIMER_FREQUENCY as u64
+}
+
+/// Packs codes of varying width into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF's variable width LZW, starting over with a clear code whenever the
+/// code table fills up.
+fn lzw_encode(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << LZW_MIN_CODE_SIZE;
+    let end = clear + 1;
+
+    let mut output = BitWriter {
+        bytes: vec![],
+        buffer: 0,
+        bits: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = LZW_MIN_CODE_SIZE + 1;
+    let mut next = end + 1;
+    output.write(clear, size);
+
+    let mut prefix = match pixels.first() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            output.write(end, size);
+            return output.finish();
+        }
+    };
+
+    for pixel in pixels[1..].iter() {
+        if let Some(code) = table.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+
+        output.write(prefix, size);
+        if next == LZW_MAX_CODE {
+            output.write(clear, size);
+            table.clear();
+            size = LZW_MIN_CODE_SIZE + 1;
+            next = end + 1;
+        } else {
+            table.insert((prefix, *pixel), next);
+            next += 1;
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        prefix = *pixel as u16;
+    }
+
+    // Decoders add a table entry for the last code as well, which can widen
+    // the end code
+    output.write(prefix, size);
+    if next < LZW_MAX_CODE && next + 1 > 1 << size && size < 12 {
+        size += 1;
+    }
+    output.write(end, size);
+    output.finish()
+}
@@ -0,0 +1,97 @@
+extern crate chip8;
+
+use chip8::cpu::CPU;
+use chip8::instructions::Instruction;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+
+/// Result and flag of 8xy4, 8xy5 and 8xy7, worked out in wider arithmetic.
+fn reference(instruction: fn(u8, u8) -> Instruction, x: u8, y: u8) -> (u8, u8) {
+    let (x, y) = (x as i32, y as i32);
+    let (result, flag) = match instruction(0, 0) {
+        Instruction::Add(..) => (x + y, x + y > 255),
+        Instruction::Subtract(..) => (x - y, x >= y),
+        Instruction::Reduce(..) => (y - x, y >= x),
+        _ => unreachable!(),
+    };
+    ((result & 0xff) as u8, flag as u8)
+}
+
+fn run(instruction: Instruction, regs: &[(usize, u8)]) -> [u8; 16] {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    for &(idx, value) in regs.iter() {
+        cpu.regs[idx] = value;
+    }
+    cpu.do_instruction(&instruction).unwrap();
+    cpu.regs
+}
+
+const ARITHMETIC: [fn(u8, u8) -> Instruction; 3] =
+    [Instruction::Add, Instruction::Subtract, Instruction::Reduce];
+
+#[test]
+fn test_arithmetic_all_operands() {
+    for instruction in ARITHMETIC.iter() {
+        for x in 0..256 {
+            for y in 0..256 {
+                let (x, y) = (x as u8, y as u8);
+                let (result, flag) = reference(*instruction, x, y);
+
+                let regs = run(instruction(0x1, 0x2), &[(0x1, x), (0x2, y), (0xf, 0xaa)]);
+                assert_eq!((regs[0x1], regs[0x2], regs[0xf]),
+                           (result, y, flag),
+                           "{} with {:#04x}, {:#04x}",
+                           instruction(0x1, 0x2),
+                           x,
+                           y);
+
+                // With VF as Vx the flag wins over the result
+                let regs = run(instruction(0xf, 0x2), &[(0xf, x), (0x2, y)]);
+                assert_eq!(regs[0xf],
+                           flag,
+                           "{} with {:#04x}, {:#04x}",
+                           instruction(0xf, 0x2),
+                           x,
+                           y);
+
+                // With VF as Vy its value is read before the flag is set
+                let regs = run(instruction(0x1, 0xf), &[(0x1, x), (0xf, y)]);
+                assert_eq!((regs[0x1], regs[0xf]),
+                           (result, flag),
+                           "{} with {:#04x}, {:#04x}",
+                           instruction(0x1, 0xf),
+                           x,
+                           y);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_arithmetic_same_register() {
+    for instruction in ARITHMETIC.iter() {
+        for x in 0..256 {
+            let x = x as u8;
+            let (result, flag) = reference(*instruction, x, x);
+            let regs = run(instruction(0x3, 0x3), &[(0x3, x)]);
+            assert_eq!((regs[0x3], regs[0xf]), (result, flag));
+        }
+    }
+}
+
+#[test]
+fn test_shifts_into_vf() {
+    for value in 0..256 {
+        let value = value as u8;
+
+        let regs = run(Instruction::ShiftRight(0xf, 0xf), &[(0xf, value)]);
+        assert_eq!(regs[0xf], value & 1);
+        let regs = run(Instruction::ShiftLeft(0xf, 0xf), &[(0xf, value)]);
+        assert_eq!(regs[0xf], value >> 7);
+
+        let regs = run(Instruction::ShiftRight(0x1, 0xf), &[(0xf, value)]);
+        assert_eq!((regs[0x1], regs[0xf]), (value >> 1, value & 1));
+        let regs = run(Instruction::ShiftLeft(0x1, 0xf), &[(0xf, value)]);
+        assert_eq!((regs[0x1], regs[0xf]), (value << 1, value >> 7));
+    }
+}
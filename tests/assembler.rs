@@ -0,0 +1,131 @@
+extern crate chip8;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use chip8::assembler::{assemble, assemble_file};
+use chip8::disassembler::Disassembly;
+use chip8::instructions::Instruction;
+
+#[test]
+fn test_assemble_instructions() {
+    let program = assemble("    CLS\n\
+                            \x20   ld v3, 0x45      ; load a constant\n\
+                            \x20   DRW V1, V2, 15\n\
+                            \x20   LD I, LONG 0x1234\n\
+                            \x20   LD [I], VA\n\
+                            \x20   ADD V0, -1\n")
+        .unwrap();
+
+    assert_eq!(program,
+               vec![0x00, 0xe0, 0x63, 0x45, 0xd1, 0x2f, 0xf0, 0x00, 0x12, 0x34, 0xfa, 0x55,
+                    0x70, 0xff]);
+}
+
+#[test]
+fn test_assemble_labels_and_constants() {
+    let program = assemble("SPEED EQU 2\n\
+                            HEIGHT EQU SPEED + 3\n\
+                            start:\n\
+                            \x20   LD I, sprite\n\
+                            \x20   CALL draw\n\
+                            \x20   JP start\n\
+                            draw: DRW V0, V1, HEIGHT\n\
+                            \x20   ADD V0, SPEED\n\
+                            \x20   RET\n\
+                            sprite:\n\
+                            \x20   DB 0b11110000, 0x90, 144\n\
+                            \x20   DW 0xf0f0, sprite - start\n")
+        .unwrap();
+
+    assert_eq!(program,
+               vec![0xa2, 0x0c, 0x22, 0x06, 0x12, 0x00, 0xd0, 0x15, 0x70, 0x02, 0x00, 0xee,
+                    0xf0, 0x90, 0x90, 0xf0, 0xf0, 0x00, 0x0c]);
+}
+
+#[test]
+fn test_assemble_include() {
+    let dir = env::temp_dir().join("chip8-assembler-include");
+    std::fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("font.asm"))
+        .unwrap()
+        .write_all(b"digit: DB 0xf0, 0x10\n")
+        .unwrap();
+    File::create(dir.join("main.asm"))
+        .unwrap()
+        .write_all(b"LD I, digit\nINCLUDE \"font.asm\"\n")
+        .unwrap();
+
+    assert_eq!(assemble_file(dir.join("main.asm")).unwrap(),
+               vec![0xa2, 0x02, 0xf0, 0x10]);
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = assemble("CLS\nJP nowhere\n").unwrap_err();
+    assert_eq!(error.to_string(),
+               "<source>:2: Unknown label or constant \"nowhere\"");
+
+    let error = assemble("LD V0, 0x100\n").unwrap_err();
+    assert_eq!(error.to_string(), "<source>:1: 0x100 is out of range");
+
+    let error = assemble("here:\nhere:\n").unwrap_err();
+    assert_eq!(error.to_string(), "<source>:2: \"here\" is already defined");
+
+    let error = assemble("SHR V0, 1\n").unwrap_err();
+    assert_eq!(error.to_string(),
+               "<source>:1: \"SHR V0, 1\" is not a valid instruction");
+}
+
+fn assert_round_trip(rom: &[u8]) {
+    let source = Disassembly::new(rom).to_string();
+    assert_eq!(assemble(&source).unwrap(), rom);
+}
+
+#[test]
+fn test_round_trip_every_opcode() {
+    // Every opcode in turn, so that each one is either disassembled into an
+    // instruction or kept as data
+    let mut rom = vec![];
+    for opcode in 0..0x10000u32 {
+        rom.push((opcode >> 8) as u8);
+        rom.push(opcode as u8);
+    }
+    assert_round_trip(&rom);
+
+    // Every instruction, reached as code one after the other
+    let mut rom = vec![];
+    for opcode in 0..0x10000u32 {
+        let opcode = opcode as u16;
+        match Instruction::from_u16(&opcode) {
+            // Leave out flow control so the rest stays reachable
+            Some(Instruction::Return) |
+            Some(Instruction::Exit) |
+            Some(Instruction::JumpToAddress(_)) |
+            Some(Instruction::CallSubroutine(_)) |
+            Some(Instruction::JumpToV0Address(_)) => continue,
+            Some(ref instruction) if instruction.to_u16() == opcode => (),
+            _ => continue,
+        }
+        rom.push((opcode >> 8) as u8);
+        rom.push(opcode as u8);
+    }
+    let last = 0x200 + rom.len() - 2;
+    assert!(Disassembly::new(&rom).code.contains(&last));
+    assert_round_trip(&rom);
+}
+
+#[test]
+fn test_round_trip_random_programs() {
+    let mut seed = 0x2545f491u32;
+    for length in 1..64 {
+        let rom: Vec<u8> = (0..length * 7)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        assert_round_trip(&rom);
+    }
+}
@@ -0,0 +1,89 @@
+extern crate chip8;
+
+use chip8::audio::{Audio, BufferAudio, Pattern, Tone, ToneGenerator, Waveform};
+
+#[test]
+fn test_square_wave() {
+    let tone = Tone {
+        frequency: 1000.0,
+        volume: 0.5,
+        waveform: Waveform::Square,
+    };
+    let mut generator = ToneGenerator::new(tone, 8000);
+    let mut out = [0.0; 16];
+    generator.fill(&mut out);
+
+    assert_eq!(&out[..8], &[0.5, 0.5, 0.5, 0.5, -0.5, -0.5, -0.5, -0.5]);
+    assert_eq!(&out[8..], &out[..8]);
+}
+
+#[test]
+fn test_waveforms_stay_within_volume() {
+    for waveform in [Waveform::Square, Waveform::Triangle, Waveform::Sawtooth, Waveform::Sine]
+        .iter() {
+        let tone = Tone {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: *waveform,
+        };
+        let mut generator = ToneGenerator::new(tone, 44100);
+        let mut out = vec![0.0; 1000];
+        generator.fill(&mut out);
+        assert!(out.iter().all(|sample| sample.abs() <= 0.25));
+        assert!(out.iter().any(|sample| *sample != 0.0));
+    }
+}
+
+#[test]
+fn test_pattern() {
+    let pattern = Pattern {
+        bits: [0xf0; 16],
+        pitch: 64,
+    };
+    assert_eq!(pattern.rate(), 4000.0);
+    assert_eq!(Pattern { pitch: 112, ..pattern }.rate(), 8000.0);
+
+    // Each bit lasts two samples at 4000 bits a second
+    let mut generator = ToneGenerator::new(Tone::default(), 8000);
+    generator.pattern = Some(pattern);
+    let mut out = [0.0; 32];
+    generator.fill(&mut out);
+
+    assert_eq!(&out[..8], &[0.25; 8]);
+    assert_eq!(&out[8..16], &[-0.25; 8]);
+    assert_eq!(&out[16..], &out[..16]);
+}
+
+#[test]
+fn test_buffer_audio_frames() {
+    let mut audio = BufferAudio::new(Tone::default(), 44100);
+    audio.update(false, None).unwrap();
+    assert_eq!(audio.samples.len(), 735);
+    assert!(audio.samples.iter().all(|sample| *sample == 0.0));
+
+    audio.update(true, None).unwrap();
+    assert_eq!(audio.samples.len(), 1470);
+    assert!(audio.samples[735..].iter().any(|sample| *sample != 0.0));
+
+    let mut audio = BufferAudio::new(Tone::default(), 22050);
+    for _ in 0..60 {
+        audio.update(true, None).unwrap();
+    }
+    assert_eq!(audio.samples.len(), 22050);
+}
+
+#[test]
+fn test_write_wav() {
+    let mut audio = BufferAudio::new(Tone::default(), 8000);
+    audio.samples = vec![1.0, -1.0, 0.0];
+
+    let mut wav = vec![];
+    audio.write_wav(&mut wav).unwrap();
+
+    assert_eq!(wav.len(), 44 + 6);
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(&wav[40..44], &[6, 0, 0, 0]);
+    assert_eq!(&wav[44..], &[0xff, 0x7f, 0x01, 0x80, 0x00, 0x00]);
+}
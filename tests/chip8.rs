@@ -1,11 +1,15 @@
 extern crate chip8;
 extern crate byteorder;
 
-use std::io::Write;
+use std::convert::TryFrom;
 
 use byteorder::{ByteOrder, WriteBytesExt, BigEndian};
 use chip8::cpu::CPU;
+use chip8::error::{CpuError, InvalidOpcode};
 use chip8::instructions::Instruction;
+use chip8::memory::AddressMode;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
 
 #[test]
 fn test_opcode_to_instruction() {
@@ -81,11 +85,49 @@ fn test_opcode_to_instruction() {
                Some(Instruction::LoadReg(0x9)));
 }
 
+#[test]
+fn test_schip_opcode_to_instruction() {
+    assert_eq!(Instruction::from_u16(&0x00c4),
+               Some(Instruction::ScrollDown(0x4)));
+    assert_eq!(Instruction::from_u16(&0x00fb), Some(Instruction::ScrollRight));
+    assert_eq!(Instruction::from_u16(&0x00fc), Some(Instruction::ScrollLeft));
+    assert_eq!(Instruction::from_u16(&0x00fd), Some(Instruction::Exit));
+    assert_eq!(Instruction::from_u16(&0x00fe), Some(Instruction::LowRes));
+    assert_eq!(Instruction::from_u16(&0x00ff), Some(Instruction::HighRes));
+    assert_eq!(Instruction::from_u16(&0xd120),
+               Some(Instruction::DrawSprite(0x1, 0x2, 0x0)));
+    assert_eq!(Instruction::from_u16(&0xf330),
+               Some(Instruction::SetMemoryForBigFont(0x3)));
+    assert_eq!(Instruction::from_u16(&0xf775),
+               Some(Instruction::StoreFlags(0x7)));
+    assert_eq!(Instruction::from_u16(&0xf785),
+               Some(Instruction::LoadFlags(0x7)));
+}
+
+#[test]
+fn test_xochip_opcode_to_instruction() {
+    assert_eq!(Instruction::from_u16(&0x00d3),
+               Some(Instruction::ScrollUp(0x3)));
+    assert_eq!(Instruction::from_u16(&0x5122),
+               Some(Instruction::SaveRange(0x1, 0x2)));
+    assert_eq!(Instruction::from_u16(&0x5123),
+               Some(Instruction::LoadRange(0x1, 0x2)));
+    assert_eq!(Instruction::from_u16(&0x5121), None);
+    assert_eq!(Instruction::from_u16(&0xf000),
+               Some(Instruction::SetLongMemoryAddress));
+    assert_eq!(Instruction::from_u16(&0xf201),
+               Some(Instruction::SelectPlane(0x2)));
+    assert_eq!(Instruction::from_u16(&0xf002),
+               Some(Instruction::LoadAudioPattern));
+    assert_eq!(Instruction::from_u16(&0xf53a),
+               Some(Instruction::SetPitch(0x5)));
+}
+
 #[test]
 fn test_clear_and_basics() {
     let mut data = [0; 2];
     BigEndian::write_u16(&mut data, 0x00e0);
-    let mut cpu = CPU::new(&data.to_vec(), None);
+    let mut cpu = CPU::new(&data.to_vec(), Platform::Chip8, Quirks::default()).unwrap();
     assert_eq!(cpu.pc, 0x200);
 
     cpu.stack.push(0x300);
@@ -101,7 +143,7 @@ fn test_clear_and_basics() {
 
 #[test]
 fn test_return() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.stack.push(0x400);
     cpu.do_instruction(&Instruction::Return).unwrap();
     assert_eq!(cpu.stack.len(), 0);
@@ -110,7 +152,7 @@ fn test_return() {
 
 #[test]
 fn test_jump_to_address() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.do_instruction(&Instruction::JumpToAddress(0x412))
         .unwrap();
     assert_eq!(cpu.pc, 0x412);
@@ -118,7 +160,7 @@ fn test_jump_to_address() {
 
 #[test]
 fn test_call_subroutine() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.pc = 0x655;
     cpu.do_instruction(&Instruction::CallSubroutine(0x595))
         .unwrap();
@@ -128,7 +170,7 @@ fn test_call_subroutine() {
 
 #[test]
 fn test_skip_if_equal() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.pc = 0x655;
     cpu.regs[0x5] = 0x23;
     cpu.do_instruction(&Instruction::SkipIfEqual(0x5, 0x23))
@@ -143,7 +185,7 @@ fn test_skip_if_equal() {
 
 #[test]
 fn test_skip_if_not_equal() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.pc = 0x655;
     cpu.regs[0x5] = 0x24;
     cpu.do_instruction(&Instruction::SkipIfEqual(0x5, 0x23))
@@ -158,7 +200,7 @@ fn test_skip_if_not_equal() {
 
 #[test]
 fn test_skip_if_equal_register() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.pc = 0x655;
     cpu.regs[0x5] = 0x14;
     cpu.regs[0x6] = 0x14;
@@ -176,7 +218,7 @@ fn test_skip_if_equal_register() {
 
 #[test]
 fn test_load_const() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0xe] = 0x0;
     cpu.do_instruction(&Instruction::LoadConst(0xe, 0x6A))
         .unwrap();
@@ -186,7 +228,7 @@ fn test_load_const() {
 
 #[test]
 fn test_add_const() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x1] = 0x12;
     cpu.do_instruction(&Instruction::AddConst(0x1, 0x13))
         .unwrap();
@@ -196,7 +238,7 @@ fn test_add_const() {
 
 #[test]
 fn test_assign_value() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x2] = 0xff;
     cpu.regs[0x3] = 0xaa;
     cpu.do_instruction(&Instruction::AssignValue(0x2, 0x3))
@@ -213,7 +255,7 @@ fn test_assign_value() {
 
 #[test]
 fn test_set_or() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x2] = 0x01;
     cpu.regs[0x3] = 0x03;
     cpu.do_instruction(&Instruction::SetOr(0x2, 0x3)).unwrap();
@@ -224,7 +266,7 @@ fn test_set_or() {
 
 #[test]
 fn test_set_and() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x2] = 0b11;
     cpu.regs[0x3] = 0b10;
     cpu.do_instruction(&Instruction::SetAnd(0x2, 0x3)).unwrap();
@@ -235,7 +277,7 @@ fn test_set_and() {
 
 #[test]
 fn test_add() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x2] = 253;
     cpu.regs[0x3] = 1;
     cpu.do_instruction(&Instruction::Add(0x2, 0x3)).unwrap();
@@ -252,7 +294,7 @@ fn test_add() {
 
 #[test]
 fn test_subtract() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x2] = 2;
     cpu.regs[0x3] = 1;
     cpu.do_instruction(&Instruction::Subtract(0x2, 0x3))
@@ -271,7 +313,7 @@ fn test_subtract() {
 
 #[test]
 fn test_shift_right() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x4] = 0b00000000;
     cpu.regs[0x5] = 0b11101110;
     cpu.do_instruction(&Instruction::ShiftRight(0x4, 0x5))
@@ -292,7 +334,7 @@ fn test_shift_right() {
 
 #[test]
 fn test_reduce() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x4] = 1;
     cpu.regs[0x5] = 243;
     cpu.do_instruction(&Instruction::Reduce(0x4, 0x5)).unwrap();
@@ -312,7 +354,7 @@ fn test_reduce() {
 
 #[test]
 fn test_shift_left() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x4] = 0b00000000;
     cpu.regs[0x5] = 0b11101110;
     cpu.do_instruction(&Instruction::ShiftLeft(0x4, 0x5))
@@ -333,7 +375,7 @@ fn test_shift_left() {
 
 #[test]
 fn test_set_memory_address() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     assert_eq!(cpu.address, 0x0);
     assert_eq!(cpu.pc, 0x200);
     cpu.do_instruction(&Instruction::SetMemoryAddress(0x2b4))
@@ -344,11 +386,11 @@ fn test_set_memory_address() {
 
 #[test]
 fn test_set_bcd() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.address = 0x0300;
     cpu.regs[0x0] = 129;
     cpu.do_instruction(&Instruction::SetBCD(0x0)).unwrap();
-    let raw = cpu.memory.clone().into_inner();
+    let raw = cpu.memory.as_slice();
     assert_eq!(raw[0x300], 1);
     assert_eq!(raw[0x301], 2);
     assert_eq!(raw[0x302], 9);
@@ -357,7 +399,7 @@ fn test_set_bcd() {
     cpu.address = 0x0400;
     cpu.regs[0x1] = 19;
     cpu.do_instruction(&Instruction::SetBCD(0x1)).unwrap();
-    let raw = cpu.memory.clone().into_inner();
+    let raw = cpu.memory.as_slice();
     assert_eq!(raw[0x400], 0);
     assert_eq!(raw[0x401], 1);
     assert_eq!(raw[0x402], 9);
@@ -366,7 +408,7 @@ fn test_set_bcd() {
     cpu.address = 0x0500;
     cpu.regs[0x2] = 8;
     cpu.do_instruction(&Instruction::SetBCD(0x2)).unwrap();
-    let raw = cpu.memory.clone().into_inner();
+    let raw = cpu.memory.as_slice();
     assert_eq!(raw[0x500], 0);
     assert_eq!(raw[0x501], 0);
     assert_eq!(raw[0x502], 8);
@@ -375,7 +417,7 @@ fn test_set_bcd() {
 
 #[test]
 fn test_dump_reg() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x0] = 0x01;
     cpu.regs[0x1] = 0x02;
     cpu.regs[0x2] = 0x03;
@@ -392,11 +434,10 @@ fn test_dump_reg() {
     cpu.regs[0xd] = 0x0e;
     cpu.regs[0xe] = 0x0f;
     cpu.address = 0x0300;
-    cpu.memory.set_position(cpu.address as u64);
     cpu.do_instruction(&Instruction::DumpReg(0xe)).unwrap();
     assert_eq!(cpu.pc, 0x202);
 
-    let raw = cpu.memory.into_inner();
+    let raw = cpu.memory.as_slice();
     assert_eq!(cpu.address, 0x30f);
     assert_eq!(raw[0x0300], 0x1);
     assert_eq!(raw[0x0301], 0x2);
@@ -417,13 +458,13 @@ fn test_dump_reg() {
 
 #[test]
 fn test_load_reg() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
 
     cpu.address = 0x0300;
-    cpu.memory.set_position(cpu.address as u64);
     cpu.memory
-        .write_all(&[0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xa, 0xb, 0xc, 0xd, 0xe, 0xf,
-                     0x10])
+        .load(0x300,
+              &[0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xa, 0xb, 0xc, 0xd, 0xe, 0xf,
+                0x10])
         .unwrap();
     cpu.do_instruction(&Instruction::LoadReg(0xe)).unwrap();
     assert_eq!(cpu.pc, 0x202);
@@ -449,7 +490,7 @@ fn test_load_reg() {
 
 #[test]
 fn test_set_memory_for_font() {
-    let mut cpu = CPU::new(&vec![], None);
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
     cpu.regs[0x0] = 0;
     cpu.do_instruction(&Instruction::SetMemoryForFont(0x0))
         .unwrap();
@@ -464,6 +505,19 @@ fn test_set_memory_for_font() {
     cpu.do_instruction(&Instruction::SetMemoryForFont(0x0))
         .unwrap();
     assert_eq!(cpu.address, 75);
+
+    // Only the low digit picks a character
+    cpu.regs[0x0] = 0xf3;
+    cpu.do_instruction(&Instruction::SetMemoryForFont(0x0))
+        .unwrap();
+    assert_eq!(cpu.address, 15);
+}
+
+#[test]
+fn test_program_too_large() {
+    assert!(CPU::new(&vec![0; 0xe00], Platform::Chip8, Quirks::default()).is_ok());
+    assert!(CPU::new(&vec![0; 0xe01], Platform::Chip8, Quirks::default()).is_err());
+    assert!(CPU::new(&vec![0; 0xe01], Platform::XoChip, Quirks::default()).is_ok());
 }
 
 #[test]
@@ -472,3 +526,609 @@ fn test_it() {
     data.write_u16::<BigEndian>(0xf029).unwrap();
     data.write_u16::<BigEndian>(0xd00f).unwrap();
 }
+
+#[test]
+fn test_resolution() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    assert_eq!((cpu.width(), cpu.height()), (64, 32));
+    assert_eq!(cpu.grid.len(), 64 * 32);
+
+    cpu.do_instruction(&Instruction::HighRes).unwrap();
+    assert!(cpu.hires);
+    assert_eq!((cpu.width(), cpu.height()), (128, 64));
+    assert_eq!(cpu.grid.len(), 128 * 64);
+
+    cpu.do_instruction(&Instruction::LowRes).unwrap();
+    assert!(!cpu.hires);
+    assert_eq!(cpu.grid.len(), 64 * 32);
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_scroll() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.grid[0] = 1;
+
+    cpu.do_instruction(&Instruction::ScrollDown(2)).unwrap();
+    assert_eq!(cpu.grid[0], 0);
+    assert_eq!(cpu.grid[2 * 64], 1);
+
+    cpu.do_instruction(&Instruction::ScrollRight).unwrap();
+    assert_eq!(cpu.grid[2 * 64], 0);
+    assert_eq!(cpu.grid[2 * 64 + 4], 1);
+
+    cpu.do_instruction(&Instruction::ScrollLeft).unwrap();
+    cpu.do_instruction(&Instruction::ScrollLeft).unwrap();
+    assert_eq!(cpu.grid.iter().filter(|p| **p != 0).count(), 0);
+}
+
+#[test]
+fn test_draw_large_sprite() {
+    let mut cpu = CPU::new(&vec![0xff; 32], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.do_instruction(&Instruction::HighRes).unwrap();
+    cpu.address = 0x200;
+    cpu.regs[0x0] = 100;
+    cpu.regs[0x1] = 10;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x0)).unwrap();
+
+    assert_eq!(cpu.grid.iter().filter(|p| **p != 0).count(), 16 * 16);
+    assert_eq!(cpu.grid[10 * 128 + 100], 1);
+    assert_eq!(cpu.grid[25 * 128 + 115], 1);
+    assert_eq!(cpu.grid[26 * 128 + 115], 0);
+    assert_eq!(cpu.regs[0xf], 0);
+}
+
+#[test]
+fn test_draw_collision_flag_covers_whole_sprite() {
+    // Only the first row overlaps what's already on screen, and the last
+    // pixel drawn turns one on
+    let mut cpu = CPU::new(&vec![0x80, 0x01], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.grid[0] = 1;
+    cpu.address = 0x200;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x2)).unwrap();
+    assert_eq!(cpu.regs[0xf], 1);
+    assert_eq!(cpu.grid[0], 0);
+    assert_eq!(cpu.grid[64 + 7], 1);
+
+    cpu.address = 0x201;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x1)).unwrap();
+    assert_eq!(cpu.regs[0xf], 0);
+}
+
+#[test]
+fn test_draw_clips_at_bottom() {
+    let mut cpu = CPU::new(&vec![0xff; 16], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.address = 0x200;
+    cpu.regs[0x0] = 28;
+    cpu.regs[0x1] = 30;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0xf)).unwrap();
+    assert_eq!(cpu.grid.iter().filter(|p| **p != 0).count(), 2 * 8);
+    assert_eq!(cpu.regs[0xf], 0);
+}
+
+#[test]
+fn test_quirk_collision_count() {
+    let mut cpu = CPU::new(&vec![0xff; 32], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.do_instruction(&Instruction::HighRes).unwrap();
+    cpu.address = 0x200;
+    cpu.regs[0x1] = 60;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x3)).unwrap();
+    assert_eq!(cpu.regs[0xf], 0);
+
+    // Three colliding rows, and 12 of the 16 rows fall off the bottom
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x0)).unwrap();
+    assert_eq!(cpu.regs[0xf], 3 + 12);
+
+    // Low resolution still only reports whether anything collided
+    cpu.do_instruction(&Instruction::LowRes).unwrap();
+    cpu.regs[0x1] = 0;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x3)).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x3)).unwrap();
+    assert_eq!(cpu.regs[0xf], 1);
+}
+
+#[test]
+fn test_set_memory_for_big_font() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.regs[0x0] = 2;
+    cpu.do_instruction(&Instruction::SetMemoryForBigFont(0x0))
+        .unwrap();
+    assert_eq!(cpu.address, 0x50 + 20);
+
+    let raw = cpu.memory.as_slice();
+    assert_eq!(&raw[0x64..0x6e], &chip8::FONT8X10[20..30]);
+}
+
+#[test]
+fn test_store_and_load_flags() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    for idx in 0..8 {
+        cpu.regs[idx] = idx as u8 + 1;
+    }
+    cpu.do_instruction(&Instruction::StoreFlags(0x7)).unwrap();
+    assert_eq!(&cpu.flags[..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+    cpu.regs = [0; 16];
+    cpu.do_instruction(&Instruction::LoadFlags(0x3)).unwrap();
+    assert_eq!(&cpu.regs[..5], &[1, 2, 3, 4, 0]);
+}
+
+#[test]
+fn test_exit() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.do_instruction(&Instruction::Exit).unwrap();
+    assert!(cpu.halted);
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn test_xochip_memory() {
+    let cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    assert_eq!(cpu.memory.as_slice().len(), 0x1000);
+
+    let cpu = CPU::new(&vec![], Platform::XoChip, Quirks::xo_chip()).unwrap();
+    assert_eq!(cpu.memory.as_slice().len(), 0x10000);
+}
+
+#[test]
+fn test_set_long_memory_address() {
+    let mut cpu = CPU::new(&vec![0xf0, 0x00, 0xbe, 0xef], Platform::XoChip, Quirks::xo_chip())
+        .unwrap();
+    let instruction = Instruction::from_u16(&cpu.fetch_opcode().unwrap()).unwrap();
+    cpu.do_instruction(&instruction).unwrap();
+    assert_eq!(cpu.address, 0xbeef);
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_skip_over_long_memory_address() {
+    let data = vec![0x30, 0x00, 0xf0, 0x00, 0x12, 0x34];
+    let mut cpu = CPU::new(&data, Platform::XoChip, Quirks::xo_chip()).unwrap();
+    cpu.do_instruction(&Instruction::SkipIfEqual(0x0, 0x0)).unwrap();
+    assert_eq!(cpu.pc, 0x206);
+
+    // The extended instructions aren't tied to a platform
+    let mut cpu = CPU::new(&data, Platform::Chip8, Quirks::default()).unwrap();
+    cpu.do_instruction(&Instruction::SkipIfEqual(0x0, 0x0)).unwrap();
+    assert_eq!(cpu.pc, 0x206);
+}
+
+#[test]
+fn test_draw_planes() {
+    let mut cpu = CPU::new(&vec![0x80, 0x40], Platform::XoChip, Quirks::xo_chip()).unwrap();
+    cpu.address = 0x200;
+
+    cpu.do_instruction(&Instruction::SelectPlane(0x3)).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x1)).unwrap();
+    assert_eq!(cpu.grid[0], 1);
+    assert_eq!(cpu.grid[1], 2);
+
+    cpu.do_instruction(&Instruction::SelectPlane(0x2)).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x1)).unwrap();
+    assert_eq!(cpu.grid[0], 3);
+    assert_eq!(cpu.grid[1], 2);
+
+    cpu.do_instruction(&Instruction::ClearDisplay).unwrap();
+    assert_eq!(cpu.grid[0], 1);
+    assert_eq!(cpu.grid[1], 0);
+}
+
+#[test]
+fn test_save_and_load_range() {
+    let mut cpu = CPU::new(&vec![], Platform::XoChip, Quirks::xo_chip()).unwrap();
+    cpu.address = 0x300;
+    cpu.regs[0x2] = 0xa;
+    cpu.regs[0x3] = 0xb;
+    cpu.regs[0x4] = 0xc;
+    cpu.do_instruction(&Instruction::SaveRange(0x4, 0x2)).unwrap();
+    assert_eq!(cpu.address, 0x300);
+    assert_eq!(&cpu.memory.as_slice()[0x300..0x303], &[0xc, 0xb, 0xa]);
+
+    cpu.do_instruction(&Instruction::LoadRange(0x7, 0x9)).unwrap();
+    assert_eq!(&cpu.regs[0x7..0xa], &[0xc, 0xb, 0xa]);
+}
+
+#[test]
+fn test_audio_pattern_and_pitch() {
+    let pattern: Vec<u8> = (0..16).collect();
+    let mut cpu = CPU::new(&pattern, Platform::XoChip, Quirks::xo_chip()).unwrap();
+    cpu.address = 0x200;
+    cpu.do_instruction(&Instruction::LoadAudioPattern).unwrap();
+    assert_eq!(cpu.audio_pattern.to_vec(), pattern);
+
+    assert_eq!(cpu.pitch, 64);
+    cpu.regs[0x1] = 112;
+    cpu.do_instruction(&Instruction::SetPitch(0x1)).unwrap();
+    assert_eq!(cpu.pitch, 112);
+}
+
+#[test]
+fn test_quirk_shift_in_place() {
+    let mut cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.regs[0x4] = 0b10000001;
+    cpu.regs[0x5] = 0b01111110;
+    cpu.do_instruction(&Instruction::ShiftRight(0x4, 0x5)).unwrap();
+    assert_eq!(cpu.regs[0x4], 0b01000000);
+    assert_eq!(cpu.regs[0xf], 1);
+
+    cpu.regs[0x4] = 0b10000001;
+    cpu.do_instruction(&Instruction::ShiftLeft(0x4, 0x5)).unwrap();
+    assert_eq!(cpu.regs[0x4], 0b00000010);
+    assert_eq!(cpu.regs[0xf], 1);
+}
+
+#[test]
+fn test_quirk_load_store_keeps_i() {
+    let mut cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.address = 0x300;
+    cpu.regs[0x0] = 0x12;
+    cpu.regs[0x1] = 0x34;
+    cpu.do_instruction(&Instruction::DumpReg(0x1)).unwrap();
+    assert_eq!(cpu.address, 0x300);
+
+    cpu.regs = [0; 16];
+    cpu.do_instruction(&Instruction::LoadReg(0x1)).unwrap();
+    assert_eq!(cpu.address, 0x300);
+    assert_eq!(&cpu.regs[..2], &[0x12, 0x34]);
+}
+
+#[test]
+fn test_quirk_jump_uses_vx() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    cpu.regs[0x0] = 0x10;
+    cpu.regs[0x3] = 0x20;
+    cpu.do_instruction(&Instruction::JumpToV0Address(0x300)).unwrap();
+    assert_eq!(cpu.pc, 0x310);
+
+    cpu.quirks = Quirks::chip48();
+    cpu.do_instruction(&Instruction::JumpToV0Address(0x300)).unwrap();
+    assert_eq!(cpu.pc, 0x320);
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    cpu.regs[0xf] = 1;
+    cpu.do_instruction(&Instruction::SetOr(0x0, 0x1)).unwrap();
+    assert_eq!(cpu.regs[0xf], 0);
+
+    cpu.quirks = Quirks::schip();
+    cpu.regs[0xf] = 1;
+    cpu.do_instruction(&Instruction::SetXor(0x0, 0x1)).unwrap();
+    assert_eq!(cpu.regs[0xf], 1);
+}
+
+#[test]
+fn test_quirk_sprite_wrap_and_clip() {
+    let mut cpu = CPU::new(&vec![0xff, 0xff], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    cpu.address = 0x200;
+    cpu.regs[0x0] = 60;
+    cpu.regs[0x1] = 31;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x2)).unwrap();
+    assert_eq!(cpu.grid.iter().filter(|p| **p != 0).count(), 4);
+    assert_eq!(cpu.grid[31 * 64 + 63], 1);
+    assert_eq!(cpu.grid[0], 0);
+
+    let mut cpu = CPU::new(&vec![0xff, 0xff], Platform::XoChip, Quirks::xo_chip()).unwrap();
+    cpu.address = 0x200;
+    cpu.regs[0x0] = 60;
+    cpu.regs[0x1] = 31;
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x2)).unwrap();
+    assert_eq!(cpu.grid.iter().filter(|p| **p != 0).count(), 16);
+    assert_eq!(cpu.grid[31 * 64 + 63], 1);
+    assert_eq!(cpu.grid[3], 1);
+    assert_eq!(cpu.grid[4], 0);
+}
+
+#[test]
+fn test_quirk_display_wait() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x1)).unwrap();
+    assert!(cpu.vblank_wait);
+    cpu.vblank();
+    assert!(!cpu.vblank_wait);
+
+    let mut cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x0, 0x1)).unwrap();
+    assert!(!cpu.vblank_wait);
+}
+
+#[test]
+fn test_timers_independent_of_instructions() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.regs[0x0] = 2;
+    cpu.do_instruction(&Instruction::SetDelay(0x0)).unwrap();
+    cpu.do_instruction(&Instruction::SetSound(0x0)).unwrap();
+    for _ in 0..10 {
+        cpu.do_instruction(&Instruction::AddConst(0x1, 0x1)).unwrap();
+    }
+    assert_eq!(cpu.delay_timer, 2);
+    assert_eq!(cpu.sound_timer, 2);
+
+    cpu.tick_timers();
+    assert_eq!(cpu.delay_timer, 1);
+    assert_eq!(cpu.sound_timer, 1);
+
+    cpu.tick_timers();
+    cpu.tick_timers();
+    assert_eq!(cpu.delay_timer, 0);
+    assert_eq!(cpu.sound_timer, 0);
+}
+
+#[test]
+fn test_skip_if_pressed() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.regs[0x1] = 0xa;
+    cpu.do_instruction(&Instruction::SkipIfPressed(0x1)).unwrap();
+    assert_eq!(cpu.pc, 0x202);
+
+    cpu.keys.press(0xa);
+    cpu.do_instruction(&Instruction::SkipIfPressed(0x1)).unwrap();
+    assert_eq!(cpu.pc, 0x206);
+    cpu.do_instruction(&Instruction::SkipIfNotPressed(0x1)).unwrap();
+    assert_eq!(cpu.pc, 0x208);
+
+    cpu.keys.release(0xa);
+    cpu.do_instruction(&Instruction::SkipIfNotPressed(0x1)).unwrap();
+    assert_eq!(cpu.pc, 0x20c);
+}
+
+#[test]
+fn test_wait_for_press() {
+    let mut cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.regs[0x3] = 0x7;
+
+    cpu.do_instruction(&Instruction::WaitForPress(0x3)).unwrap();
+    cpu.do_instruction(&Instruction::WaitForPress(0x3)).unwrap();
+    assert_eq!(cpu.pc, 0x200);
+
+    cpu.keys.press(0xc);
+    cpu.do_instruction(&Instruction::WaitForPress(0x3)).unwrap();
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.regs[0x3], 0xc);
+    assert!(!cpu.waiting_for_key);
+}
+
+#[test]
+fn test_wait_for_press_ignores_held_keys() {
+    let mut cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    cpu.keys.press(0x1);
+
+    cpu.do_instruction(&Instruction::WaitForPress(0x0)).unwrap();
+    cpu.do_instruction(&Instruction::WaitForPress(0x0)).unwrap();
+    assert_eq!(cpu.pc, 0x200);
+
+    cpu.keys.release(0x1);
+    cpu.keys.press(0x1);
+    cpu.do_instruction(&Instruction::WaitForPress(0x0)).unwrap();
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.regs[0x0], 0x1);
+}
+
+#[test]
+fn test_wait_for_release() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    cpu.do_instruction(&Instruction::WaitForPress(0x5)).unwrap();
+
+    cpu.keys.press(0x9);
+    cpu.do_instruction(&Instruction::WaitForPress(0x5)).unwrap();
+    assert_eq!(cpu.pc, 0x200);
+
+    cpu.keys.release(0x9);
+    cpu.do_instruction(&Instruction::WaitForPress(0x5)).unwrap();
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.regs[0x5], 0x9);
+}
+
+#[test]
+fn test_step() {
+    let mut cpu = CPU::new(&vec![0x61, 0x23, 0x12, 0x00], Platform::Chip8, Quirks::default())
+        .unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.regs[0x1], 0x23);
+    assert_eq!(cpu.pc, 0x202);
+
+    cpu.step().unwrap();
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn test_step_unknown_opcode() {
+    let mut cpu = CPU::new(&vec![0x61, 0x23, 0xff, 0xff], Platform::Chip8, Quirks::default())
+        .unwrap();
+    cpu.step().unwrap();
+
+    let error = cpu.step().unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::UnknownOpcode {
+                   opcode: 0xffff,
+                   pc: 0x202,
+               }));
+    assert_eq!(format!("{}", error), "Unknown opcode 0xffff at 0x202");
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    assert_eq!(cpu.stack_depth, 12);
+    for _ in 0..12 {
+        cpu.do_instruction(&Instruction::CallSubroutine(0x300)).unwrap();
+    }
+
+    let error = cpu.do_instruction(&Instruction::CallSubroutine(0x300)).unwrap_err();
+    let mut trace = vec![0x200];
+    trace.extend(vec![0x300; 11]);
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::StackOverflow {
+                   pc: 0x300,
+                   trace,
+               }));
+    assert_eq!(cpu.stack.len(), 12);
+    assert_eq!(cpu.pc, 0x300);
+
+    let cpu = CPU::new(&vec![], Platform::SuperChip, Quirks::schip()).unwrap();
+    assert_eq!(cpu.stack_depth, 16);
+}
+
+#[test]
+fn test_stack_underflow() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.pc = 0x246;
+    let error = cpu.do_instruction(&Instruction::Return).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::StackUnderflow { pc: 0x246 }));
+    assert_eq!(format!("{}", error), "Stack underflow at 0x246");
+    assert_eq!(cpu.pc, 0x246);
+}
+
+#[test]
+fn test_memory_wraps() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.address = 0xffe;
+    cpu.regs[0x0] = 0x11;
+    cpu.regs[0x1] = 0x22;
+    cpu.regs[0x2] = 0x33;
+    cpu.do_instruction(&Instruction::DumpReg(0x2)).unwrap();
+    assert_eq!(cpu.memory.read(0xffe).unwrap(), 0x11);
+    assert_eq!(cpu.memory.read(0xfff).unwrap(), 0x22);
+    assert_eq!(cpu.memory.read(0x000).unwrap(), 0x33);
+    assert_eq!(cpu.memory.read_u16(0x1fff).unwrap(), 0x2233);
+
+    cpu.address = 0xffff;
+    cpu.regs[0x3] = 0x02;
+    cpu.do_instruction(&Instruction::AddOffset(0x3)).unwrap();
+    assert_eq!(cpu.address, 0x0001);
+}
+
+#[test]
+fn test_wrap_keeps_pc_and_i_in_memory() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.address = 0xffe;
+    cpu.regs[0x0] = 0x04;
+    cpu.do_instruction(&Instruction::AddOffset(0x0)).unwrap();
+    assert_eq!(cpu.address, 0x002);
+
+    cpu.do_instruction(&Instruction::JumpToV0Address(0xffe)).unwrap();
+    assert_eq!(cpu.pc, 0x002);
+
+    cpu.pc = 0xffe;
+    cpu.do_instruction(&Instruction::LoadConst(0x1, 0x01)).unwrap();
+    assert_eq!(cpu.pc, 0x000);
+
+    // Addresses are left to fault when wrapping is off
+    cpu.memory.address_mode = AddressMode::Fault;
+    cpu.pc = 0xffe;
+    cpu.do_instruction(&Instruction::LoadConst(0x1, 0x01)).unwrap();
+    assert_eq!(cpu.pc, 0x1000);
+    assert!(cpu.step().is_err());
+}
+
+#[test]
+fn test_memory_faults() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.memory.address_mode = AddressMode::Fault;
+    cpu.address = 0xffe;
+    let error = cpu.do_instruction(&Instruction::SetBCD(0x0)).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::AddressOutOfRange { address: 0x1000 }));
+    assert_eq!(format!("{}", error), "Address 0x1000 is out of range");
+
+    let error = cpu.do_instruction(&Instruction::LoadReg(0x2)).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::AddressOutOfRange { address: 0x1000 }));
+
+    cpu.address = 0xfff;
+    let error = cpu.do_instruction(&Instruction::DrawSprite(0x0, 0x1, 0x2))
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::AddressOutOfRange { address: 0x1000 }));
+
+    assert!(cpu.memory.load(0xfff, &[0x1, 0x2]).is_err());
+}
+
+#[test]
+fn test_memory_write_protection() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.memory.protect_interpreter = true;
+    cpu.address = 0x1fe;
+    cpu.regs[0x0] = 0xff;
+    let error = cpu.do_instruction(&Instruction::DumpReg(0x0)).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpuError>(),
+               Some(&CpuError::WriteProtected { address: 0x1fe }));
+    assert_eq!(format!("{}", error), "Write to protected address 0x1fe");
+    assert_eq!(cpu.memory.read(0x1fe).unwrap(), 0x00);
+
+    cpu.address = 0x200;
+    cpu.do_instruction(&Instruction::SetBCD(0x0)).unwrap();
+    assert_eq!(&cpu.memory.as_slice()[0x200..0x203], &[2, 5, 5]);
+
+    cpu.memory.protect_interpreter = false;
+    cpu.address = 0x1fe;
+    cpu.do_instruction(&Instruction::DumpReg(0x0)).unwrap();
+    assert_eq!(cpu.memory.read(0x1fe).unwrap(), 0xff);
+}
+
+#[test]
+fn test_encode_decode_round_trip() {
+    for opcode in 0..0x10000u32 {
+        let opcode = opcode as u16;
+        match Instruction::try_from(opcode) {
+            Ok(instruction) => {
+                // Unused bits are ignored, so only the canonical encoding
+                // comes back out, and it decodes to the same instruction
+                let canonical = instruction.to_u16();
+                assert_eq!(Instruction::from_u16(&canonical), Some(instruction));
+                assert_eq!(Instruction::from_u16(&canonical).unwrap().to_u16(), canonical);
+            }
+            Err(e) => {
+                assert_eq!(e, InvalidOpcode(opcode));
+                assert_eq!(Instruction::from_u16(&opcode), None);
+            }
+        }
+    }
+
+    assert_eq!(Instruction::from_u16(&0x01e0), Some(Instruction::ClearDisplay));
+    assert_eq!(Instruction::from_u16(&0x9121),
+               Some(Instruction::SkipIfNotEqualRegister(0x1, 0x2)));
+    assert_eq!(format!("{}", Instruction::try_from(0x5121).unwrap_err()),
+               "Invalid opcode 0x5121");
+}
+
+#[test]
+fn test_patch_memory() {
+    let mut cpu = CPU::new(&vec![0x00, 0xe0], Platform::Chip8, Quirks::default()).unwrap();
+    cpu.memory.protect_interpreter = true;
+    cpu.memory.patch(0x200, &Instruction::LoadConst(0x3, 0x45)).unwrap();
+    cpu.memory.patch(0x1fe, &Instruction::AddConst(0x3, 0x01)).unwrap();
+    assert_eq!(cpu.memory.read_u16(0x200).unwrap(), 0x6345);
+
+    cpu.pc = 0x1fe;
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.regs[0x3], 0x45);
+    assert!(cpu.memory.patch(0xfff, &Instruction::ClearDisplay).is_err());
+    assert!(cpu.memory.patch(0x200, &Instruction::SetLongMemoryAddress).is_err());
+    assert_eq!(cpu.memory.read_u16(0x200).unwrap(), 0x6345);
+}
+
+#[test]
+fn test_seeded_random() {
+    let mut first = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap().with_seed(42);
+    let mut second = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap().with_seed(42);
+
+    let mut values = vec![];
+    for _ in 0..16 {
+        first.do_instruction(&Instruction::BitwiseRandom(0x0, 0xff)).unwrap();
+        second.do_instruction(&Instruction::BitwiseRandom(0x0, 0xff)).unwrap();
+        assert_eq!(first.regs[0x0], second.regs[0x0]);
+        values.push(first.regs[0x0]);
+    }
+    values.dedup();
+    assert!(values.len() > 1);
+
+    for _ in 0..16 {
+        first.do_instruction(&Instruction::BitwiseRandom(0x1, 0x0f)).unwrap();
+        assert_eq!(first.regs[0x1] & 0xf0, 0);
+    }
+}
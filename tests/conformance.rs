@@ -0,0 +1,368 @@
+extern crate chip8;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::assembler::assemble;
+use chip8::cpu::CPU;
+use chip8::display::TextRenderer;
+use chip8::emulator::Emulator;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+
+/// When set, tests write the screens they end on as their golden images
+/// rather than comparing against them.
+const UPDATE_GOLDEN: &str = "CHIP8_UPDATE_GOLDEN";
+
+/// Draws every hex digit of the font, in two rows.
+const FONT: &str = "
+    LD V0, 0
+    LD V1, 1
+    LD V2, 1
+small:
+    LD F, V0
+    DRW V1, V2, 5
+    ADD V0, 1
+    ADD V1, 6
+    SE V0, 8
+    JP next
+    LD V1, 1
+    LD V2, 8
+next:
+    SE V0, 16
+    JP small
+done:
+    JP done
+";
+
+/// Shows the flag, or result, of each arithmetic edge case as a digit.
+const FLAGS: &str = "
+    LD VD, 1
+    LD VE, 1
+    ; Carry, and no carry
+    LD V1, 255
+    LD V2, 1
+    ADD V1, V2
+    CALL show
+    LD V1, 1
+    ADD V1, V2
+    CALL show
+    ; Equal operands don't borrow
+    LD V1, 5
+    LD V2, 5
+    SUB V1, V2
+    CALL show
+    LD V1, 4
+    SUB V1, V2
+    CALL show
+    LD V1, 5
+    SUBN V1, V2
+    CALL show
+    LD V1, 6
+    SUBN V1, V2
+    CALL show
+    ; Bits shifted out
+    LD V1, 0x01
+    SHR V1, V1
+    CALL show
+    LD V1, 0x80
+    SHL V1, V1
+    CALL show
+    ; VF as Vx ends up holding the flag
+    LD VD, 1
+    LD VE, 8
+    LD VF, 200
+    LD V1, 100
+    ADD VF, V1
+    CALL show
+    LD VF, 10
+    LD V1, 3
+    SUB VF, V1
+    CALL show
+    LD VF, 3
+    SUBN VF, V1
+    CALL show
+    ; VF as Vy is read before the flag is set
+    LD V1, 2
+    LD VF, 3
+    ADD V1, VF
+    LD VF, V1
+    CALL show
+    LD V1, 9
+    LD VF, 2
+    SUB V1, VF
+    LD VF, V1
+    CALL show
+done:
+    JP done
+
+show:
+    LD F, VF
+    DRW VD, VE, 5
+    ADD VD, 5
+    RET
+";
+
+/// Draws a box over each edge and corner of the screen, and a digit for
+/// whether the last of them collided.
+const SPRITES: &str = "
+    LD I, box
+    LD V0, 60
+    LD V1, 10
+    DRW V0, V1, 8
+    LD V0, 20
+    LD V1, 28
+    DRW V0, V1, 8
+    LD V0, 60
+    DRW V0, V1, 8
+    LD V0, 62
+    LD V1, 30
+    DRW V0, V1, 8
+    LD V2, VF
+    LD F, V2
+    LD V0, 30
+    LD V1, 12
+    DRW V0, V1, 5
+done:
+    JP done
+box:
+    DB 0xff, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xff
+";
+
+/// Reads three keys with `LD Vx, K` and draws them in a row, then draws a 7
+/// once key 7 is held down and an E once it's let go again.
+const KEYPAD: &str = "
+    LD V3, 0
+    LD V4, 0
+read:
+    LD V0, K
+    LD F, V0
+    DRW V3, V4, 5
+    ADD V3, 5
+    SE V3, 15
+    JP read
+
+    LD V1, 7
+    LD V3, 0
+    LD V4, 10
+held:
+    SKP V1
+    JP held
+    LD F, V1
+    DRW V3, V4, 5
+    LD V3, 5
+released:
+    SKNP V1
+    JP released
+    LD V2, 0xe
+    LD F, V2
+    DRW V3, V4, 5
+done:
+    JP done
+";
+
+/// Frames on which `KEYPAD` has a key pressed, or released.
+const KEY_EVENTS: [(usize, u8, bool); 8] = [(5, 0xa, true),
+                                            (8, 0xa, false),
+                                            (15, 0x3, true),
+                                            (18, 0x3, false),
+                                            (25, 0xf, true),
+                                            (28, 0xf, false),
+                                            (35, 0x7, true),
+                                            (45, 0x7, false)];
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.txt", name))
+}
+
+fn run(rom: &Vec<u8>, platform: Platform, quirks: Quirks, frames: usize) -> String {
+    run_emulator(Emulator::new(CPU::new(rom, platform, quirks).unwrap()), frames, &[])
+}
+
+/// Runs `frames` frames, pressing and releasing keys at the start of the
+/// frames `key_events` gives, and returns the screen it ends on.
+fn run_emulator(mut emulator: Emulator,
+                frames: usize,
+                key_events: &[(usize, u8, bool)])
+                -> String {
+    for frame in 0..frames {
+        for &(_, key, pressed) in key_events.iter().filter(|event| event.0 == frame) {
+            if pressed {
+                emulator.cpu.keys.press(key);
+            } else {
+                emulator.cpu.keys.release(key);
+            }
+        }
+        emulator.run_frame().unwrap();
+    }
+
+    let mut screen = TextRenderer::default();
+    emulator.cpu.show(&mut screen).unwrap();
+    screen.text
+}
+
+/// Compares a screen with its golden image, or updates the image when
+/// `UPDATE_GOLDEN` is set.
+fn check_screen(name: &str, screen: &str) {
+    let path = golden_path(name);
+    if env::var_os(UPDATE_GOLDEN).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, screen).unwrap();
+        return;
+    }
+
+    let golden = match fs::read_to_string(&path) {
+        Ok(golden) => golden,
+        Err(_) => {
+            panic!("No golden image at {}, run with {}=1 to create it",
+                   path.display(),
+                   UPDATE_GOLDEN)
+        }
+    };
+    if golden != screen {
+        panic!("{} doesn't match its golden image\nexpected:\n{}\nactual:\n{}",
+               name,
+               golden,
+               screen);
+    }
+}
+
+#[test]
+fn test_font() {
+    let rom = assemble(FONT).unwrap();
+    check_screen("font", &run(&rom, Platform::Chip8, Quirks::cosmac_vip(), 60));
+}
+
+#[test]
+fn test_flags() {
+    let rom = assemble(FLAGS).unwrap();
+    check_screen("flags", &run(&rom, Platform::Chip8, Quirks::cosmac_vip(), 60));
+}
+
+#[test]
+fn test_sprites_clip() {
+    let rom = assemble(SPRITES).unwrap();
+    check_screen("sprites-clip", &run(&rom, Platform::Chip8, Quirks::cosmac_vip(), 60));
+}
+
+#[test]
+fn test_keypad() {
+    let rom = assemble(KEYPAD).unwrap();
+    let cpu = CPU::new(&rom, Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    check_screen("keypad", &run_emulator(Emulator::new(cpu), 60, &KEY_EVENTS));
+}
+
+#[test]
+fn test_sprites_wrap() {
+    let rom = assemble(SPRITES).unwrap();
+    check_screen("sprites-wrap", &run(&rom, Platform::XoChip, Quirks::xo_chip(), 60));
+}
+
+/// Directory holding the community test ROMs, which aren't distributed with
+/// the emulator. `test_community_roms` only runs when asked for, and looks
+/// in `ROMS_DIR` when this isn't set.
+const ROMS: &str = "CHIP8_TEST_ROMS";
+
+/// Where the community test ROMs are looked for by default, ignored by git.
+const ROMS_DIR: &str = "tests/roms";
+
+/// A test ROM from one of the community suites.
+struct TestRom {
+    file: &'static str,
+    platform: Platform,
+    quirks: fn() -> Quirks,
+    frames: usize,
+    /// Value poked into 0x1ff, which Timendus' quirks and keypad tests read
+    /// to pick the platform or test without waiting for a key.
+    select: Option<u8>,
+    /// Frames on which a key is pressed, or released.
+    keys: &'static [(usize, u8, bool)],
+}
+
+/// Presses and releases a key for the `Fx0A` test of Timendus' keypad ROM.
+const GET_KEY_EVENTS: [(usize, u8, bool); 2] = [(30, 0x5, true), (35, 0x5, false)];
+
+const COMMUNITY_ROMS: [TestRom; 7] = [
+    TestRom {
+        file: "2-ibm-logo.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::cosmac_vip,
+        frames: 60,
+        select: None,
+        keys: &[],
+    },
+    TestRom {
+        file: "3-corax+.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::cosmac_vip,
+        frames: 120,
+        select: None,
+        keys: &[],
+    },
+    TestRom {
+        file: "4-flags.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::cosmac_vip,
+        frames: 300,
+        select: None,
+        keys: &[],
+    },
+    TestRom {
+        file: "5-quirks.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::cosmac_vip,
+        frames: 600,
+        select: Some(1),
+        keys: &[],
+    },
+    TestRom {
+        file: "5-quirks.ch8",
+        platform: Platform::SuperChip,
+        quirks: Quirks::schip,
+        frames: 600,
+        select: Some(2),
+        keys: &[],
+    },
+    TestRom {
+        file: "BC_test.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::chip48,
+        frames: 120,
+        select: None,
+        keys: &[],
+    },
+    TestRom {
+        file: "6-keypad.ch8",
+        platform: Platform::Chip8,
+        quirks: Quirks::cosmac_vip,
+        frames: 120,
+        select: Some(3),
+        keys: &GET_KEY_EVENTS,
+    },
+];
+
+#[test]
+#[ignore]
+fn test_community_roms() {
+    let roms = match env::var_os(ROMS) {
+        Some(roms) => PathBuf::from(roms),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join(ROMS_DIR),
+    };
+
+    for test in COMMUNITY_ROMS.iter() {
+        let path = roms.join(test.file);
+        let rom = match fs::read(&path) {
+            Ok(rom) => rom,
+            Err(e) => panic!("Can't read {}: {}", path.display(), e),
+        };
+
+        let name = format!("{}-{:?}", test.file.trim_end_matches(".ch8"), test.platform);
+        let cpu = CPU::new(&rom, test.platform, (test.quirks)()).unwrap();
+        let mut emulator = Emulator::new(cpu);
+        if let Some(select) = test.select {
+            emulator.cpu.memory.load(0x1ff, &[select]).unwrap();
+        }
+        check_screen(&name, &run_emulator(emulator, test.frames, test.keys));
+    }
+}
@@ -0,0 +1,102 @@
+extern crate chip8;
+
+use chip8::cpu::CPU;
+use chip8::debugger::{Debugger, Stop};
+use chip8::emulator::Emulator;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+
+// 0x200: CALL 0x208
+// 0x202: LD V1, 0x02
+// 0x204: JP 0x204
+// 0x206: (padding)
+// 0x208: LD V0, 0x01
+// 0x20a: LD I, 0x300
+// 0x20c: LD [I], V0
+// 0x20e: RET
+fn program() -> Vec<u8> {
+    vec![0x22, 0x08, 0x61, 0x02, 0x12, 0x04, 0x00, 0x00, 0x60, 0x01, 0xa3, 0x00, 0xf0, 0x55,
+         0x00, 0xee]
+}
+
+fn debugger() -> Debugger {
+    let cpu = CPU::new(&program(), Platform::Chip8, Quirks::default()).unwrap();
+    Debugger::new(Emulator::new(cpu))
+}
+
+#[test]
+fn test_breakpoint() {
+    let mut debugger = debugger();
+    debugger.breakpoints.insert(0x20c);
+    assert_eq!(debugger.run(100).unwrap(), Stop::Breakpoint(0x20c));
+    assert_eq!(debugger.emulator.cpu.pc, 0x20c);
+    assert_eq!(debugger.cycles, 3);
+
+    // Continuing from a breakpoint moves past it
+    debugger.breakpoints.insert(0x204);
+    assert_eq!(debugger.run(100).unwrap(), Stop::Breakpoint(0x204));
+    assert_eq!(debugger.run(10).unwrap(), Stop::Breakpoint(0x204));
+}
+
+#[test]
+fn test_watchpoints() {
+    let mut debugger = debugger();
+    debugger.register_watchpoints.insert(0x0);
+    assert_eq!(debugger.run(100).unwrap(),
+               Stop::RegisterWatch {
+                   register: 0x0,
+                   old: 0x00,
+                   new: 0x01,
+               });
+    assert_eq!(debugger.emulator.cpu.pc, 0x20a);
+
+    debugger.memory_watchpoints.insert(0x300);
+    assert_eq!(debugger.run(100).unwrap(),
+               Stop::MemoryWatch {
+                   address: 0x300,
+                   old: 0x00,
+                   new: 0x01,
+               });
+    assert_eq!(debugger.emulator.cpu.pc, 0x20e);
+
+    assert_eq!(debugger.run(100).unwrap(), Stop::StepLimit);
+}
+
+#[test]
+fn test_step_over_and_out() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.step_over(100).unwrap(), Stop::Stepped);
+    assert_eq!(debugger.emulator.cpu.pc, 0x202);
+    assert_eq!(debugger.emulator.cpu.regs[0x0], 0x01);
+    assert!(debugger.emulator.cpu.stack.is_empty());
+    assert!(debugger.step_out(100).is_err());
+
+    let mut debugger = self::debugger();
+    debugger.step().unwrap();
+    assert_eq!(debugger.emulator.cpu.pc, 0x208);
+    assert_eq!(debugger.stack_trace(), "#0 0x200");
+    assert_eq!(debugger.step_out(100).unwrap(), Stop::Stepped);
+    assert_eq!(debugger.emulator.cpu.pc, 0x202);
+    assert_eq!(debugger.stack_trace(), "Stack is empty");
+}
+
+#[test]
+fn test_inspection() {
+    let mut debugger = debugger();
+    debugger.emulator.cpu.regs[0xa] = 0xbc;
+    debugger.emulator.cpu.delay_timer = 0x3c;
+    assert_eq!(debugger.registers(),
+               "V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00\n\
+                V8=00 V9=00 VA=bc VB=00 VC=00 VD=00 VE=00 VF=00\n\
+                I=0000 PC=0200 DT=3c ST=00");
+    assert_eq!(debugger.dump_memory(0x200, 18).unwrap(),
+               "0200: 22 08 61 02 12 04 00 00 60 01 a3 00 f0 55 00 ee\n\
+                0210: 00 00");
+    assert_eq!(debugger.dump_memory(0xffe, 16).unwrap(), "0ffe: 00 00");
+    assert_eq!(debugger.dump_memory(0xffe, usize::MAX).unwrap(), "0ffe: 00 00");
+    assert!(debugger.dump_memory(usize::MAX, 16).is_err());
+
+    // The timers keep counting down while stepping
+    debugger.run(debugger.emulator.instructions_per_frame as usize * 2).unwrap();
+    assert_eq!(debugger.emulator.cpu.delay_timer, 0x3a);
+}
@@ -0,0 +1,94 @@
+extern crate chip8;
+
+use chip8::disassembler::Disassembly;
+use chip8::instructions::Instruction;
+
+#[test]
+fn test_mnemonics() {
+    let mnemonics = [(0x00e0, "CLS"),
+                     (0x00ee, "RET"),
+                     (0x00c4, "SCD 4"),
+                     (0x00fd, "EXIT"),
+                     (0x1234, "JP 0x234"),
+                     (0x2468, "CALL 0x468"),
+                     (0x3345, "SE V3, 0x45"),
+                     (0x5120, "SE V1, V2"),
+                     (0x5122, "SAVE V1, V2"),
+                     (0x6345, "LD V3, 0x45"),
+                     (0x8ab4, "ADD VA, VB"),
+                     (0x8127, "SUBN V1, V2"),
+                     (0x812e, "SHL V1, V2"),
+                     (0xa123, "LD I, 0x123"),
+                     (0xf000, "LD I, LONG"),
+                     (0xb300, "JP V0, 0x300"),
+                     (0xc10f, "RND V1, 0x0f"),
+                     (0xd12f, "DRW V1, V2, 15"),
+                     (0xe39e, "SKP V3"),
+                     (0xf30a, "LD V3, K"),
+                     (0xf31e, "ADD I, V3"),
+                     (0xf330, "LD HF, V3"),
+                     (0xf355, "LD [I], V3"),
+                     (0xf365, "LD V3, [I]"),
+                     (0xf385, "LD V3, R"),
+                     (0xf201, "PLANE 2"),
+                     (0xf002, "AUDIO")];
+
+    for &(opcode, mnemonic) in mnemonics.iter() {
+        assert_eq!(Instruction::from_u16(&opcode).unwrap().to_string(), mnemonic);
+    }
+}
+
+#[test]
+fn test_disassemble_control_flow() {
+    // 0x200: CALL 0x208
+    // 0x202: SE V0, 0x01
+    // 0x204: JP 0x200
+    // 0x206: RET (skipped to by SE)
+    // 0x208: RET
+    // 0x20a: sprite data that happens to decode
+    let rom = vec![0x22, 0x08, 0x30, 0x01, 0x12, 0x00, 0x00, 0xee, 0x00, 0xee, 0xa5, 0x5a, 0x3c];
+    let disassembly = Disassembly::new(&rom);
+    assert_eq!(disassembly.code.iter().cloned().collect::<Vec<_>>(),
+               vec![0x200, 0x202, 0x204, 0x206, 0x208]);
+    assert_eq!(disassembly.labels.iter().cloned().collect::<Vec<_>>(),
+               vec![0x200, 0x208]);
+
+    assert_eq!(disassembly.to_string(),
+               "L200:\n\
+               \x20   CALL L208               ; 200: 2208\n\
+               \x20   SE V0, 0x01             ; 202: 3001\n\
+               \x20   JP L200                 ; 204: 1200\n\
+               \x20   RET                     ; 206: 00ee\n\
+               L208:\n\
+               \x20   RET                     ; 208: 00ee\n\
+               \x20   DW 0xa55a               ; 20a: a55a\n\
+               \x20   DB 0x3c                 ; 20c: 3c\n");
+}
+
+#[test]
+fn test_disassemble_unknown_words() {
+    // An unknown opcode ends the path, so everything after it is data
+    let rom = vec![0x60, 0x01, 0xff, 0xff, 0x60, 0x02];
+    let disassembly = Disassembly::new(&rom);
+    assert_eq!(disassembly.code.iter().cloned().collect::<Vec<_>>(), vec![0x200]);
+    assert!(disassembly.labels.is_empty());
+    assert_eq!(disassembly.to_string(),
+               "    LD V0, 0x01             ; 200: 6001\n\
+               \x20   DW 0xffff               ; 202: ffff\n\
+               \x20   DW 0x6002               ; 204: 6002\n");
+}
+
+#[test]
+fn test_disassemble_long_address() {
+    // LD I, LONG 0x1234 skipped over by SNE, then a jump into a table
+    let rom = vec![0x40, 0x00, 0xf0, 0x00, 0x12, 0x34, 0xb2, 0x08, 0x12, 0x08];
+    let disassembly = Disassembly::new(&rom);
+    assert_eq!(disassembly.code.iter().cloned().collect::<Vec<_>>(),
+               vec![0x200, 0x202, 0x206, 0x208]);
+    assert_eq!(disassembly.to_string(),
+               "    SNE V0, 0x00            ; 200: 4000\n\
+               \x20   LD I, LONG 0x1234       ; 202: f0001234\n\
+               \x20   JP V0, L208             ; 206: b208\n\
+               L208:\n\
+               \x20   JP L208                 ; 208: 1208\n");
+}
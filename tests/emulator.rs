@@ -0,0 +1,125 @@
+extern crate chip8;
+
+use chip8::assembler::assemble;
+use chip8::cpu::CPU;
+use chip8::emulator::Emulator;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+
+/// Counts instructions in V0 forever.
+fn counter(instructions_per_frame: u32) -> Emulator {
+    let rom = assemble("loop:\n ADD V0, 1\n JP loop").unwrap();
+    let cpu = CPU::new(&rom, Platform::Chip8, Quirks::default()).unwrap();
+    Emulator::new(cpu).with_instructions_per_frame(instructions_per_frame)
+}
+
+#[test]
+fn test_run_frame_executes_instructions_per_frame() {
+    for &ipf in [7, 15, 30].iter() {
+        let mut emulator = counter(ipf);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.regs[0] as u32, ipf.div_ceil(2));
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cpu.regs[0] as u32, ipf);
+        assert_eq!(emulator.frames, 2);
+    }
+}
+
+#[test]
+fn test_run_frame_ticks_timers_once() {
+    let mut emulator = counter(1000);
+    emulator.cpu.delay_timer = 10;
+    emulator.cpu.sound_timer = 1;
+
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu.delay_timer, 9);
+    assert_eq!(emulator.cpu.sound_timer, 0);
+    assert!(emulator.beeping);
+
+    emulator.run_frame().unwrap();
+    assert!(!emulator.beeping);
+}
+
+#[test]
+fn test_speed_changes_between_frames() {
+    let mut emulator = counter(2);
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu.regs[0], 1);
+
+    emulator.instructions_per_frame = 20;
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu.regs[0], 11);
+}
+
+#[test]
+fn test_display_wait_ends_frame() {
+    let rom = assemble("loop:\n DRW V0, V0, 1\n ADD V1, 1\n JP loop").unwrap();
+    let cpu = CPU::new(&rom, Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    let mut emulator = Emulator::new(cpu).with_instructions_per_frame(100);
+
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu.regs[1], 0);
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu.regs[1], 1);
+}
+
+#[test]
+fn test_run_frame_errors() {
+    let rom = vec![0xff, 0xff, 0x60, 0x01];
+    let cpu = CPU::new(&rom, Platform::Chip8, Quirks::default()).unwrap();
+    let mut emulator = Emulator::new(cpu).with_instructions_per_frame(2);
+
+    emulator.cpu.delay_timer = 5;
+    assert!(emulator.run_frame().is_err());
+    assert_eq!(emulator.cpu.delay_timer, 5);
+
+    emulator.run_frame_with(|cpu, _| {
+            cpu.pc += 2;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(emulator.cpu.regs[0], 1);
+    assert_eq!(emulator.cpu.delay_timer, 4);
+}
+
+#[test]
+fn test_pattern_only_on_xo_chip() {
+    for &platform in [Platform::Chip8, Platform::XoChip].iter() {
+        let rom = assemble("LD V0, 112\n PITCH V0\n LD I, data\n AUDIO\nloop:\n JP loop\n\
+                            data:\n DB 0xF0").unwrap();
+        let cpu = CPU::new(&rom, platform, Quirks::default()).unwrap();
+        let mut emulator = Emulator::new(cpu);
+        assert_eq!(emulator.pattern(), None);
+
+        emulator.run_frame().unwrap();
+        match emulator.pattern() {
+            Some(pattern) => {
+                assert_eq!(platform, Platform::XoChip);
+                assert_eq!(pattern.bits[0], 0xf0);
+                assert_eq!(pattern.pitch, 112);
+            }
+            None => assert_eq!(platform, Platform::Chip8),
+        }
+    }
+}
+
+#[test]
+fn test_step_ends_frames() {
+    let mut emulator = counter(3);
+    emulator.cpu.delay_timer = 10;
+    for _ in 0..2 {
+        emulator.step().unwrap();
+    }
+    assert_eq!(emulator.frames, 0);
+    assert_eq!(emulator.cpu.delay_timer, 10);
+
+    emulator.step().unwrap();
+    assert_eq!(emulator.frames, 1);
+    assert_eq!(emulator.cpu.delay_timer, 9);
+
+    // Frames run by either carry on from the other
+    emulator.step().unwrap();
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.frames, 2);
+    assert_eq!(emulator.cpu.regs[0], 3);
+}
@@ -0,0 +1,62 @@
+extern crate chip8;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use chip8::keymap::KeyMap;
+
+#[test]
+fn test_default_layout() {
+    let map = KeyMap::default();
+    let rows: Vec<Vec<&str>> = [[0x1, 0x2, 0x3, 0xc],
+                                [0x4, 0x5, 0x6, 0xd],
+                                [0x7, 0x8, 0x9, 0xe],
+                                [0xa, 0x0, 0xb, 0xf]]
+        .iter()
+        .map(|row| row.iter().map(|key| map.keys[*key].as_str()).collect())
+        .collect();
+
+    assert_eq!(rows,
+               vec![vec!["1", "2", "3", "4"],
+                    vec!["Q", "W", "E", "R"],
+                    vec!["A", "S", "D", "F"],
+                    vec!["Z", "X", "C", "V"]]);
+}
+
+#[test]
+fn test_parse_overrides_defaults() {
+    let map = KeyMap::parse("# arrows for movement\n\
+                             4 = Left\n\
+                             6 = Right\n\
+                             \n\
+                             f=Return\n")
+        .unwrap();
+
+    assert_eq!(map.keys[0x4], "Left");
+    assert_eq!(map.keys[0x6], "Right");
+    assert_eq!(map.keys[0xf], "Return");
+    assert_eq!(map.keys[0x5], "W");
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(KeyMap::parse("10 = Q").is_err());
+    assert!(KeyMap::parse("g = Q").is_err());
+    assert!(KeyMap::parse("4").is_err());
+    assert!(KeyMap::parse("4 = ").is_err());
+}
+
+#[test]
+fn test_for_rom() {
+    let rom = env::temp_dir().join("chip8-keymap-test.ch8");
+    assert_eq!(KeyMap::path_for_rom(&rom), env::temp_dir().join("chip8-keymap-test.ch8.keys"));
+    assert_eq!(KeyMap::for_rom(&rom).unwrap(), KeyMap::default());
+
+    let path = KeyMap::path_for_rom(&rom);
+    File::create(&path).unwrap().write_all(b"0 = Space\n").unwrap();
+    let map = KeyMap::for_rom(&rom).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(map.keys[0x0], "Space");
+}
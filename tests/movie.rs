@@ -0,0 +1,87 @@
+extern crate chip8;
+
+use chip8::assembler::assemble;
+use chip8::cpu::CPU;
+use chip8::display::TextRenderer;
+use chip8::emulator::Emulator;
+use chip8::movie::Movie;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+
+/// Waits for a key, then draws its digit somewhere random.
+const PROGRAM: &str = "
+loop:
+    LD V0, K
+    LD F, V0
+    RND V1, 0x3f
+    RND V2, 0x1f
+    DRW V1, V2, 5
+    JP loop
+";
+
+fn screen(cpu: &CPU) -> String {
+    let mut screen = TextRenderer::default();
+    cpu.show(&mut screen).unwrap();
+    screen.text
+}
+
+/// Plays 120 frames of 10 instructions, tapping a different key every 10
+/// frames, while recording them into a movie.
+fn record(rom: &Vec<u8>) -> (CPU, Movie) {
+    let cpu = CPU::new(rom, Platform::Chip8, Quirks::cosmac_vip()).unwrap().with_seed(99);
+    let mut emulator = Emulator::new(cpu).with_instructions_per_frame(10);
+    let mut movie = Movie::new(rom, &emulator);
+
+    for frame in 0..120 {
+        match frame % 10 {
+            0 => emulator.cpu.keys.press((frame / 10) as u8),
+            3 => emulator.cpu.keys.release((frame / 10) as u8),
+            _ => (),
+        }
+
+        movie.record_frame(&emulator.cpu.keys);
+        emulator.run_frame().unwrap();
+    }
+
+    (emulator.cpu, movie)
+}
+
+#[test]
+fn test_replay_reproduces_screen() {
+    let rom = assemble(PROGRAM).unwrap();
+    let (cpu, movie) = record(&rom);
+    assert!(screen(&cpu).contains('#'));
+
+    let replayed = movie.replay(&rom).unwrap().cpu;
+    assert_eq!(screen(&replayed), screen(&cpu));
+    assert_eq!(replayed.grid, cpu.grid);
+    assert_eq!(replayed.regs, cpu.regs);
+}
+
+#[test]
+fn test_save_and_load_movie() {
+    let rom = assemble(PROGRAM).unwrap();
+    let (_, movie) = record(&rom);
+
+    let mut saved = vec![];
+    movie.save(&mut saved).unwrap();
+    let loaded = Movie::load(&mut saved.as_slice()).unwrap();
+    assert_eq!(loaded, movie);
+    assert_eq!(loaded.frames.len(), 120);
+
+    assert!(Movie::load(&mut &b"C8TR"[..]).is_err());
+
+    // A frame count far beyond the data is an error, not an allocation
+    saved.truncate(31);
+    saved[27..31].copy_from_slice(&[0xff; 4]);
+    assert!(Movie::load(&mut saved.as_slice()).is_err());
+}
+
+#[test]
+fn test_replay_rejects_other_program() {
+    let rom = assemble(PROGRAM).unwrap();
+    let (_, movie) = record(&rom);
+
+    let other = assemble("JP 0x200").unwrap();
+    assert!(movie.replay(&other).is_err());
+}
@@ -0,0 +1,92 @@
+extern crate chip8;
+
+use chip8::display::Palette;
+use chip8::screenshot::{CaptureOptions, ImageFormat, Screenshot};
+
+/// A 2x2 screen with one pixel in each plane combination.
+fn screenshot(scale: usize) -> Screenshot {
+    Screenshot::new(&[0, 1, 2, 3], 2, 2, scale, Palette::default())
+}
+
+#[test]
+fn test_scale() {
+    let screenshot = screenshot(2);
+    assert_eq!((screenshot.width, screenshot.height), (4, 4));
+    assert_eq!(screenshot.pixels,
+               vec![0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 3]);
+}
+
+#[test]
+fn test_pbm_and_pgm() {
+    let mut pbm = vec![];
+    screenshot(1).write(&mut pbm, ImageFormat::Pbm).unwrap();
+    assert_eq!(pbm, b"P4\n2 2\n\x80\x40".to_vec());
+
+    let mut pgm = vec![];
+    screenshot(1).write(&mut pgm, ImageFormat::Pgm).unwrap();
+    assert_eq!(pgm, b"P5\n2 2\n255\n\x00\xff\xaa\x55".to_vec());
+}
+
+#[test]
+fn test_png() {
+    let mut png = vec![];
+    screenshot(1).write_png(&mut png).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    // IHDR: 2x2, 8 bit indexed colour
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 2, 8, 3, 0, 0, 0]);
+    assert_eq!(&png[29..33], &[0x45, 0x68, 0xfd, 0x16]);
+
+    // PLTE with the four colours
+    assert_eq!(&png[33..41], b"\x00\x00\x00\x0cPLTE");
+    assert_eq!(&png[41..53],
+               &[0, 0, 0, 255, 255, 255, 170, 170, 170, 85, 85, 85]);
+
+    // IDAT with one stored block holding each row behind a filter byte
+    let idat = 57;
+    assert_eq!(&png[idat + 4..idat + 8], b"IDAT");
+    let zlib = &png[idat + 8..idat + 8 + 17];
+    assert_eq!(&zlib[..7], &[0x78, 0x01, 0x01, 0x06, 0x00, 0xf9, 0xff]);
+    assert_eq!(&zlib[7..13], &[0, 0, 1, 0, 2, 3]);
+    assert_eq!(&zlib[13..17], &[0x00, 0x11, 0x00, 0x07]);
+
+    assert_eq!(&png[png.len() - 12..],
+               &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+}
+
+#[test]
+fn test_formats() {
+    assert_eq!(ImageFormat::from_path("shot.PNG").unwrap(), ImageFormat::Png);
+    assert_eq!(ImageFormat::from_path("shot.pgm").unwrap(), ImageFormat::Pgm);
+    assert!(ImageFormat::from_path("shot.jpg").is_err());
+    assert!(ImageFormat::from_path("shot").is_err());
+}
+
+#[test]
+fn test_palette() {
+    let palette: Palette = "102030, #ffffff".parse().unwrap();
+    assert_eq!(palette.color(0), (0x10, 0x20, 0x30));
+    assert_eq!(palette.color(1), (0xff, 0xff, 0xff));
+    assert_eq!(palette.color(3), Palette::default().color(3));
+
+    assert!("000000".parse::<Palette>().is_err());
+    assert!("000000,fff".parse::<Palette>().is_err());
+}
+
+#[test]
+fn test_capture_options() {
+    let mut capture = CaptureOptions::new(8);
+    let mut args = vec!["3", "112233,445566", "a.gif", "game.ch8"].into_iter().map(String::from);
+    assert!(capture.parse_arg("--scale", &mut args).unwrap());
+    assert!(capture.parse_arg("--palette", &mut args).unwrap());
+    assert!(capture.parse_arg("--video", &mut args).unwrap());
+    assert!(!capture.parse_arg("game.ch8", &mut args).unwrap());
+    assert_eq!(capture.scale, 3);
+    assert_eq!(capture.palette.colors[1], (0x44, 0x55, 0x66));
+    assert_eq!(capture.video_path, Some("a.gif".to_string()));
+    assert_eq!(args.next(), Some("game.ch8".to_string()));
+
+    assert!(capture.parse_arg("--video", &mut args).is_err());
+    assert!(capture.parse_arg("--scale", &mut vec!["0".to_string()].into_iter()).is_err());
+}
@@ -0,0 +1,123 @@
+extern crate chip8;
+
+use std::path::PathBuf;
+
+use chip8::cpu::CPU;
+use chip8::instructions::Instruction;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+use chip8::state;
+
+fn busy_cpu() -> CPU {
+    let mut cpu = CPU::new(&vec![0xf0, 0x90, 0xf0], Platform::SuperChip, Quirks::schip()).unwrap()
+        .with_seed(1234);
+    cpu.regs = [0x10; 16];
+    cpu.regs[0x3] = 0x42;
+    cpu.address = 0x200;
+    cpu.pc = 0x234;
+    cpu.stack = vec![0x202, 0x210];
+    cpu.delay_timer = 12;
+    cpu.sound_timer = 3;
+    cpu.keys.press(0xa);
+    cpu.flags[0x7] = 0x99;
+    cpu.do_instruction(&Instruction::HighRes).unwrap();
+    cpu.do_instruction(&Instruction::DrawSprite(0x3, 0x3, 0x3)).unwrap();
+    cpu
+}
+
+#[test]
+fn test_save_and_load_state() {
+    let cpu = busy_cpu();
+    let mut snapshot = vec![];
+    cpu.save_state(&mut snapshot).unwrap();
+
+    let mut restored = CPU::new(&vec![], Platform::Chip8, Quirks::cosmac_vip()).unwrap();
+    restored.load_state(&mut snapshot.as_slice()).unwrap();
+
+    assert_eq!(restored.platform, Platform::SuperChip);
+    assert_eq!(restored.quirks, Quirks::schip());
+    assert_eq!(restored.regs, cpu.regs);
+    assert_eq!(restored.address, cpu.address);
+    assert_eq!(restored.pc, cpu.pc);
+    assert_eq!(restored.stack, cpu.stack);
+    assert_eq!(restored.stack_depth, 16);
+    assert_eq!(restored.delay_timer, 12);
+    assert_eq!(restored.sound_timer, 3);
+    assert!(restored.keys.is_pressed(0xa));
+    assert_eq!(restored.flags, cpu.flags);
+    assert!(restored.hires);
+    assert_eq!(restored.grid, cpu.grid);
+    assert_eq!(restored.memory.as_slice(), cpu.memory.as_slice());
+    assert_eq!(restored.rng, cpu.rng);
+
+    let mut resaved = vec![];
+    restored.save_state(&mut resaved).unwrap();
+    assert_eq!(resaved, snapshot);
+}
+
+#[test]
+fn test_load_state_rejects_bad_data() {
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    assert!(cpu.load_state(&mut &b"nope"[..]).is_err());
+
+    let mut snapshot = vec![];
+    busy_cpu().save_state(&mut snapshot).unwrap();
+    snapshot[4] = 0xff;
+    assert!(cpu.load_state(&mut snapshot.as_slice()).is_err());
+
+    snapshot[4] = 1;
+    snapshot.truncate(snapshot.len() - 1);
+    assert!(cpu.load_state(&mut snapshot.as_slice()).is_err());
+    assert_eq!(cpu.platform, Platform::Chip8);
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn test_load_state_checks_screen_size() {
+    let mut snapshot = vec![];
+    busy_cpu().save_state(&mut snapshot).unwrap();
+    let mut cpu = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+
+    // The screen's size follows the stack, the timers, keys, flags, audio
+    // pattern and generator state
+    for len in [0xffff_ffffu32, 64 * 32].iter() {
+        let mut bad = snapshot.clone();
+        bad[88..92].copy_from_slice(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8,
+                                      *len as u8]);
+        assert!(cpu.load_state(&mut bad.as_slice()).is_err());
+        assert_eq!(cpu.platform, Platform::Chip8);
+        assert!(!cpu.hires);
+    }
+}
+
+#[test]
+fn test_load_state_checks_stack_depth() {
+    let mut cpu = busy_cpu();
+    cpu.platform = Platform::Chip8;
+    cpu.stack = vec![0x200; 13];
+    let mut snapshot = vec![];
+    cpu.save_state(&mut snapshot).unwrap();
+
+    let mut restored = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    assert!(restored.load_state(&mut snapshot.as_slice()).is_err());
+    assert!(restored.stack.is_empty());
+}
+
+#[test]
+fn test_random_numbers_continue_after_load() {
+    let mut cpu = busy_cpu();
+    let mut snapshot = vec![];
+    cpu.save_state(&mut snapshot).unwrap();
+    cpu.do_instruction(&Instruction::BitwiseRandom(0x0, 0xff)).unwrap();
+
+    let mut restored = CPU::new(&vec![], Platform::Chip8, Quirks::default()).unwrap();
+    restored.load_state(&mut snapshot.as_slice()).unwrap();
+    restored.do_instruction(&Instruction::BitwiseRandom(0x0, 0xff)).unwrap();
+    assert_eq!(restored.regs[0x0], cpu.regs[0x0]);
+}
+
+#[test]
+fn test_path_for_rom() {
+    assert_eq!(state::path_for_rom("games/BRIX", 2),
+               PathBuf::from("games/BRIX.state2"));
+}
@@ -0,0 +1,96 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::cpu::CPU;
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+use chip8::trace::{read_binary_trace, TraceEntry, TraceFormat, Tracer};
+
+use common::SharedBuffer;
+
+// 0x200: LD V3, 0x45
+// 0x202: LD I, 0x300
+// 0x204: LD DT, V3
+// 0x206: CALL 0x20a
+// 0x208: (unreached)
+// 0x20a: ADD V3, 0x01
+fn cpu() -> CPU {
+    let program = vec![0x63, 0x45, 0xa3, 0x00, 0xf3, 0x15, 0x22, 0x0a, 0x00, 0x00, 0x73, 0x01];
+    CPU::new(&program, Platform::Chip8, Quirks::default()).unwrap()
+}
+
+fn run(cpu: &mut CPU, steps: usize) {
+    for _ in 0..steps {
+        cpu.step().unwrap();
+    }
+}
+
+#[test]
+fn test_text_trace() {
+    let buffer = SharedBuffer::default();
+    let mut cpu = cpu();
+    cpu.tracer = Some(Tracer::new(buffer.clone(), TraceFormat::Text).unwrap());
+    run(&mut cpu, 5);
+
+    let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    assert_eq!(text,
+               "200: 6345  LD V3, 0x45           V3 00->45  DT=00 ST=00\n\
+                202: a300  LD I, 0x300           I 0000->0300  DT=00 ST=00\n\
+                204: f315  LD DT, V3             DT=45 ST=00\n\
+                206: 220a  CALL 0x20a            DT=45 ST=00\n\
+                20a: 7301  ADD V3, 0x01          V3 45->46  DT=45 ST=00\n");
+}
+
+#[test]
+fn test_binary_trace() {
+    let buffer = SharedBuffer::default();
+    let mut cpu = cpu();
+    cpu.tracer = Some(Tracer::new(buffer.clone(), TraceFormat::Binary).unwrap());
+    run(&mut cpu, 5);
+
+    let data = buffer.0.borrow().clone();
+    // Header, then 9 bytes per record plus 2 for each register and 4 for I
+    assert_eq!(data.len(), 5 + 5 * 9 + 2 * 2 + 4);
+
+    let entries = read_binary_trace(&mut &data[..]).unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[1],
+               TraceEntry {
+                   pc: 0x202,
+                   opcode: 0xa300,
+                   registers: vec![],
+                   address: Some((0x0000, 0x0300)),
+                   delay_timer: 0x00,
+                   sound_timer: 0x00,
+               });
+    assert_eq!(entries[4].registers, vec![(0x3, 0x45, 0x46)]);
+    assert_eq!(entries[4].delay_timer, 0x45);
+
+    assert!(read_binary_trace(&mut &b"C8ST\x01"[..]).is_err());
+}
+
+#[test]
+fn test_trace_filters() {
+    let buffer = SharedBuffer::default();
+    let mut cpu = cpu();
+    let mut tracer = Tracer::new(buffer.clone(), TraceFormat::Binary).unwrap();
+    tracer.pc_range = Some(0x202..0x20a);
+    cpu.tracer = Some(tracer);
+    run(&mut cpu, 5);
+
+    let entries = read_binary_trace(&mut &buffer.0.borrow()[..]).unwrap();
+    let pcs: Vec<usize> = entries.iter().map(|entry| entry.pc).collect();
+    assert_eq!(pcs, vec![0x202, 0x204, 0x206]);
+
+    let buffer = SharedBuffer::default();
+    let mut cpu = self::cpu();
+    let mut tracer = Tracer::new(buffer.clone(), TraceFormat::Binary).unwrap();
+    tracer.mnemonics = vec!["call".to_string(), "ADD".to_string()];
+    cpu.tracer = Some(tracer);
+    run(&mut cpu, 5);
+
+    let entries = read_binary_trace(&mut &buffer.0.borrow()[..]).unwrap();
+    let pcs: Vec<usize> = entries.iter().map(|entry| entry.pc).collect();
+    assert_eq!(pcs, vec![0x206, 0x20a]);
+}
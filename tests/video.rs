@@ -0,0 +1,220 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::display::Palette;
+use chip8::rng::Rng;
+use chip8::video::{VideoFormat, VideoRecorder};
+
+use common::SharedBuffer;
+
+struct Gif {
+    width: usize,
+    height: usize,
+    /// Each frame's delay and pixels.
+    frames: Vec<(u16, Vec<u8>)>,
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    data[at] as u16 | (data[at + 1] as u16) << 8
+}
+
+fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    let initial: Vec<Vec<u8>> = (0..end + 1).map(|code| vec![code as u8]).collect();
+
+    let mut table = initial.clone();
+    let mut size = min_code_size + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut output = vec![];
+    let mut position = 0;
+    loop {
+        let mut code = 0;
+        for bit in 0..size as usize {
+            let at = position + bit;
+            code |= ((data[at / 8] >> (at % 8)) as usize & 1) << bit;
+        }
+        position += size as usize;
+
+        if code == clear {
+            table = initial.clone();
+            size = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return output;
+        }
+
+        let entry = if code < table.len() {
+            table[code].clone()
+        } else {
+            let mut entry = previous.clone().unwrap();
+            entry.push(entry[0]);
+            entry
+        };
+        output.extend_from_slice(&entry);
+
+        if let Some(mut added) = previous {
+            if table.len() < 4096 {
+                added.push(entry[0]);
+                table.push(added);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+        }
+        previous = Some(entry);
+    }
+}
+
+fn decode_gif(data: &[u8]) -> Gif {
+    assert_eq!(&data[..6], b"GIF89a");
+    let width = read_u16(data, 6) as usize;
+    let height = read_u16(data, 8) as usize;
+    assert_eq!(data[10], 0x81);
+
+    let mut at = 13 + 4 * 3;
+    let mut delay = 0;
+    let mut frames = vec![];
+    loop {
+        match data[at] {
+            0x21 => {
+                if data[at + 1] == 0xf9 {
+                    delay = read_u16(data, at + 4);
+                }
+                at += 2;
+                while data[at] != 0 {
+                    at += data[at] as usize + 1;
+                }
+                at += 1;
+            }
+            0x2c => {
+                assert_eq!(read_u16(data, at + 5) as usize, width);
+                assert_eq!(read_u16(data, at + 7) as usize, height);
+                let min_code_size = data[at + 10];
+                at += 11;
+
+                let mut compressed = vec![];
+                while data[at] != 0 {
+                    let length = data[at] as usize;
+                    compressed.extend_from_slice(&data[at + 1..at + 1 + length]);
+                    at += length + 1;
+                }
+                at += 1;
+
+                let pixels = lzw_decode(&compressed, min_code_size);
+                assert_eq!(pixels.len(), width * height);
+                frames.push((delay, pixels));
+            }
+            0x3b => break,
+            other => panic!("Unexpected block {:#04x}", other),
+        }
+    }
+
+    Gif {
+        width,
+        height,
+        frames,
+    }
+}
+
+fn filled(value: u8) -> Vec<u8> {
+    vec![value; 64 * 32]
+}
+
+#[test]
+fn test_gif_delays_follow_emulated_frames() {
+    let buffer = SharedBuffer::default();
+    let mut recorder = VideoRecorder::new(buffer.clone(), VideoFormat::Gif, 1, Palette::default())
+        .unwrap();
+
+    // Four frames of one screen, a single frame too short for a GIF to
+    // show, then a second of another
+    for _ in 0..4 {
+        recorder.capture_grid(&filled(0), 64, 32).unwrap();
+    }
+    recorder.capture_grid(&filled(1), 64, 32).unwrap();
+    for _ in 0..56 {
+        recorder.capture_grid(&filled(2), 64, 32).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    // Low resolution frames are doubled up to the high resolution size
+    let gif = decode_gif(&buffer.0.borrow());
+    assert_eq!((gif.width, gif.height), (128, 64));
+    assert_eq!(gif.frames.len(), 2);
+    assert_eq!(gif.frames[0], (7, vec![0; 128 * 64]));
+    assert_eq!(gif.frames[1], (95, vec![2; 128 * 64]));
+}
+
+#[test]
+fn test_gif_round_trips_noise() {
+    // Enough noise to fill the code table several times over
+    let mut rng = Rng::new(7);
+    let grid: Vec<u8> = (0..128 * 64).map(|_| rng.next_u8() & 0x3).collect();
+
+    let buffer = SharedBuffer::default();
+    let mut recorder = VideoRecorder::new(buffer.clone(), VideoFormat::Gif, 2, Palette::default())
+        .unwrap();
+    recorder.capture_grid(&grid, 128, 64).unwrap();
+    recorder.finish().unwrap();
+
+    let gif = decode_gif(&buffer.0.borrow());
+    assert_eq!((gif.width, gif.height), (256, 128));
+    let pixels = &gif.frames[0].1;
+    for y in 0..128 {
+        for x in 0..256 {
+            assert_eq!(pixels[y * 256 + x], grid[(y / 2) * 128 + x / 2]);
+        }
+    }
+}
+
+#[test]
+fn test_resolution_change_keeps_size() {
+    let buffer = SharedBuffer::default();
+    let mut recorder = VideoRecorder::new(buffer.clone(), VideoFormat::Gif, 2, Palette::default())
+        .unwrap();
+    let mut lores = filled(0);
+    lores[0] = 1;
+    recorder.capture_grid(&lores, 64, 32).unwrap();
+    recorder.capture_grid(&vec![3; 128 * 64], 128, 64).unwrap();
+    recorder.capture_grid(&vec![3; 128 * 64], 128, 64).unwrap();
+    recorder.finish().unwrap();
+
+    let gif = decode_gif(&buffer.0.borrow());
+    assert_eq!((gif.width, gif.height), (256, 128));
+    for y in 0..5 {
+        let row = &gif.frames[0].1[y * 256..];
+        let set = if y < 4 { 1 } else { 0 };
+        assert_eq!(&row[..5], &[set, set, set, set, 0]);
+    }
+    assert_eq!(gif.frames[1].1, vec![3; 256 * 128]);
+}
+
+#[test]
+fn test_scale_too_large() {
+    assert!(VideoRecorder::new(vec![], VideoFormat::Gif, 511, Palette::default()).is_ok());
+    assert!(VideoRecorder::new(vec![], VideoFormat::Gif, 512, Palette::default()).is_err());
+}
+
+#[test]
+fn test_raw_frames() {
+    let buffer = SharedBuffer::default();
+    let palette: Palette = "102030,405060".parse().unwrap();
+    let mut recorder = VideoRecorder::new(buffer.clone(), VideoFormat::Raw, 1, palette).unwrap();
+    recorder.capture_grid(&filled(0), 64, 32).unwrap();
+    recorder.capture_grid(&filled(1), 64, 32).unwrap();
+    recorder.capture_grid(&filled(1), 64, 32).unwrap();
+    recorder.finish().unwrap();
+
+    let raw = buffer.0.borrow();
+    assert_eq!(raw.len(), 3 * 128 * 64 * 3);
+    assert_eq!(&raw[..3], &[0x10, 0x20, 0x30]);
+    assert_eq!(&raw[raw.len() - 3..], &[0x40, 0x50, 0x60]);
+
+    assert!(VideoFormat::from_path("run.gif").is_ok());
+    assert_eq!(VideoFormat::from_path("run.RGB").unwrap(), VideoFormat::Raw);
+    assert!(VideoFormat::from_path("run.mp4").is_err());
+}